@@ -1,11 +1,8 @@
-use std::future;
-
 use stremio_addon_sdk::builder::{Builder, HandlerKind};
-use stremio_addon_sdk::futures::future::BoxFuture;
+use stremio_addon_sdk::error::HandlerResult;
+use stremio_addon_sdk::manifest::ManifestBuilder;
 use stremio_addon_sdk::server::{serve_serverless, ServerOptions};
-use stremio_addon_sdk::stremio_core::types::addon::{
-    Manifest, ManifestResource, ResourceResponse, Version,
-};
+use stremio_addon_sdk::stremio_core::types::addon::{ResourcePath, ResourceResponse, Version};
 use stremio_addon_sdk::stremio_core::types::resource::{Stream, StreamSource};
 use stremio_addon_sdk::url::Url;
 use stremio_addon_sdk::vercel_runtime::{Body, Error, Request, Response, run};
@@ -18,42 +15,40 @@ async fn main() -> Result<(), Error> {
 pub async fn handler_serverless(
     req: Request,
 ) -> Result<Response<Body>, Box<dyn std::error::Error + Send + Sync + 'static>> {
-    let manifest = Manifest {
-        id: "org.example.addon".into(),
-        version: Version::new(1, 0, 0),
-        name: "Example".into(),
-        contact_email: None,
-        resources: vec![ManifestResource::Short("stream".into())],
-        types: vec!["movie".into()],
-        catalogs: vec![],
-        addon_catalogs: vec![],
-        background: Some(Url::parse("https://i.imgur.com/P3JQEmD.jpg").unwrap()),
-        logo: Some(Url::parse("https://i.imgur.com/M6pQlDh.jpg").unwrap()),
-        description: Some("Example Addon".into()),
-        id_prefixes: None,
-        behavior_hints: Default::default(),
-    };
+    let manifest = ManifestBuilder::new("org.example.addon", "Example")
+        .version(Version::new(1, 0, 0))
+        .description("Example Addon")
+        .logo(Url::parse("https://i.imgur.com/M6pQlDh.jpg").unwrap())
+        .background(Url::parse("https://i.imgur.com/P3JQEmD.jpg").unwrap())
+        .types(["movie"])
+        .resource("stream")
+        .build()?;
     let options = ServerOptions::default();
-    let router = Builder::new(manifest).handler(HandlerKind::Stream, |req| -> BoxFuture<Option<ResourceResponse>>{
-        println!("Stream: {}/{}/{}/{:?}", req.resource, req.r#type, req.id, req.extra);
-        if req.r#type == "movie" && req.id == "tt1254207" {
-            Box::pin(future::ready(Some(ResourceResponse::Streams {
-                streams: vec![Stream {
-                    source: StreamSource::Url {
-                        url: Url::parse("http://distribution.bbb3d.renderfarming.net/video/mp4/bbb_sunflower_1080p_30fps_normal.mp4").unwrap()
-                    },
-                    name: None,
-                    description: None,
-                    thumbnail: None,
-                    subtitles: vec![],
-                    behavior_hints: Default::default(),
-                }],
-            })))
-        } else {
-            Box::pin(future::ready(Some(ResourceResponse::Streams {
-                streams: vec![],
-            })))
-        }
-    }).build(options);
+    let router = Builder::new(manifest)
+        .handler(HandlerKind::Stream, handle_stream)
+        .build(options);
     serve_serverless(router, req).await
 }
+
+async fn handle_stream(req: ResourcePath) -> HandlerResult {
+    println!(
+        "Stream: {}/{}/{}/{:?}",
+        req.resource, req.r#type, req.id, req.extra
+    );
+    if req.r#type == "movie" && req.id == "tt1254207" {
+        Ok(ResourceResponse::Streams {
+            streams: vec![Stream {
+                source: StreamSource::Url {
+                    url: Url::parse("http://distribution.bbb3d.renderfarming.net/video/mp4/bbb_sunflower_1080p_30fps_normal.mp4").unwrap()
+                },
+                name: None,
+                description: None,
+                thumbnail: None,
+                subtitles: vec![],
+                behavior_hints: Default::default(),
+            }],
+        })
+    } else {
+        Ok(ResourceResponse::Streams { streams: vec![] })
+    }
+}
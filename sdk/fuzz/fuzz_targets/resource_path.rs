@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use stremio_addon_sdk::parser::{format_resource_path, parse_resource_path};
+
+// whatever parses must format back to a path that parses to the same value
+fuzz_target!(|path: &str| {
+    if let Ok(parsed) = parse_resource_path(path) {
+        let formatted = format_resource_path(&parsed);
+        assert_eq!(parse_resource_path(&formatted), Ok(parsed), "{}", formatted);
+    }
+});
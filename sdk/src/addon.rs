@@ -0,0 +1,398 @@
+use std::future::{self, Future};
+
+use stremio_core::types::addon::{Manifest, ResourcePath};
+
+use crate::builder::HandlerKind;
+use crate::context::RequestContext;
+use crate::error::{HandlerError, HandlerResult};
+use crate::state::State;
+
+/// Addon implemented as a type, built into a router with `Builder::from_addon`.
+///
+/// Each resource method comes with a constant, e.g. `STREAM` for `stream`, which is set to `true`
+/// together with overriding the method. The builder serves the methods whose constant is set, and
+/// adds the `meta`, `stream` and `subtitles` resources among them to `manifest.resources` when the
+/// manifest does not declare them.
+///
+/// ```no_run
+/// # use stremio_addon_sdk::addon::Addon;
+/// # use stremio_addon_sdk::builder::Builder;
+/// # use stremio_addon_sdk::context::RequestContext;
+/// # use stremio_addon_sdk::error::HandlerResult;
+/// # use stremio_addon_sdk::manifest::ManifestBuilder;
+/// # use stremio_addon_sdk::server::ServerOptions;
+/// # use stremio_addon_sdk::stremio_core::types::addon::{Manifest, ResourcePath, ResourceResponse};
+/// struct Movies;
+///
+/// impl Addon for Movies {
+///     const STREAM: bool = true;
+///
+///     fn manifest(&self) -> Manifest {
+///         ManifestBuilder::new("com.example.movies", "Movies")
+///             .types(["movie"])
+///             .build()
+///             .unwrap()
+///     }
+///
+///     async fn stream(&self, path: &ResourcePath, ctx: &RequestContext) -> HandlerResult {
+///         Ok(ResourceResponse::Streams { streams: vec![] })
+///     }
+/// }
+///
+/// let router = Builder::from_addon(Movies).build(ServerOptions::default());
+/// ```
+///
+/// A constant set without its method being overridden fails to compile once the addon is built:
+///
+/// ```compile_fail
+/// # use stremio_addon_sdk::addon::Addon;
+/// # use stremio_addon_sdk::builder::Builder;
+/// # use stremio_addon_sdk::manifest::ManifestBuilder;
+/// # use stremio_addon_sdk::server::ServerOptions;
+/// # use stremio_addon_sdk::stremio_core::types::addon::Manifest;
+/// struct Movies;
+///
+/// impl Addon for Movies {
+///     const STREAM: bool = true;
+///
+///     fn manifest(&self) -> Manifest {
+///         ManifestBuilder::new("com.example.movies", "Movies")
+///             .types(["movie"])
+///             .build()
+///             .unwrap()
+///     }
+/// }
+///
+/// let router = Builder::from_addon(Movies).build(ServerOptions::default());
+/// ```
+///
+/// A method overridden without its constant being set is never called. When the manifest declares
+/// its resource, building fails with a `ValidationIssue::MissingHandler` for it.
+pub trait Addon: Send + Sync + 'static {
+    /// Whether `catalog` is overridden.
+    const CATALOG: bool = false;
+    /// Whether `meta` is overridden.
+    const META: bool = false;
+    /// Whether `stream` is overridden.
+    const STREAM: bool = false;
+    /// Whether `subtitles` is overridden.
+    const SUBTITLES: bool = false;
+    /// Whether `addon_catalog` is overridden.
+    const ADDON_CATALOG: bool = false;
+
+    fn manifest(&self) -> Manifest;
+
+    fn catalog(
+        &self,
+        _path: &ResourcePath,
+        _ctx: &RequestContext,
+    ) -> impl Future<Output = HandlerResult> + Send {
+        let () = <Self as NotOverridden>::CATALOG_UNSET;
+        future::ready(Err(HandlerError::NotFound))
+    }
+
+    fn meta(
+        &self,
+        _path: &ResourcePath,
+        _ctx: &RequestContext,
+    ) -> impl Future<Output = HandlerResult> + Send {
+        let () = <Self as NotOverridden>::META_UNSET;
+        future::ready(Err(HandlerError::NotFound))
+    }
+
+    fn stream(
+        &self,
+        _path: &ResourcePath,
+        _ctx: &RequestContext,
+    ) -> impl Future<Output = HandlerResult> + Send {
+        let () = <Self as NotOverridden>::STREAM_UNSET;
+        future::ready(Err(HandlerError::NotFound))
+    }
+
+    fn subtitles(
+        &self,
+        _path: &ResourcePath,
+        _ctx: &RequestContext,
+    ) -> impl Future<Output = HandlerResult> + Send {
+        let () = <Self as NotOverridden>::SUBTITLES_UNSET;
+        future::ready(Err(HandlerError::NotFound))
+    }
+
+    fn addon_catalog(
+        &self,
+        _path: &ResourcePath,
+        _ctx: &RequestContext,
+    ) -> impl Future<Output = HandlerResult> + Send {
+        let () = <Self as NotOverridden>::ADDON_CATALOG_UNSET;
+        future::ready(Err(HandlerError::NotFound))
+    }
+}
+
+/// Checks, in the default methods of `Addon`, that their constant is not set. The checks are only
+/// evaluated for the default methods an addon keeps, so that setting the constant of a method
+/// which is not overridden is a compile error rather than a resource answering 404.
+trait NotOverridden: Addon {
+    const CATALOG_UNSET: () = assert!(
+        !Self::CATALOG,
+        "Addon::CATALOG is set, but catalog is not overridden"
+    );
+    const META_UNSET: () = assert!(
+        !Self::META,
+        "Addon::META is set, but meta is not overridden"
+    );
+    const STREAM_UNSET: () = assert!(
+        !Self::STREAM,
+        "Addon::STREAM is set, but stream is not overridden"
+    );
+    const SUBTITLES_UNSET: () = assert!(
+        !Self::SUBTITLES,
+        "Addon::SUBTITLES is set, but subtitles is not overridden"
+    );
+    const ADDON_CATALOG_UNSET: () = assert!(
+        !Self::ADDON_CATALOG,
+        "Addon::ADDON_CATALOG is set, but addon_catalog is not overridden"
+    );
+}
+
+impl<A: Addon + ?Sized> NotOverridden for A {}
+
+/// Kinds of the resources whose methods `A` overrides.
+pub(crate) fn kinds<A: Addon>() -> Vec<HandlerKind> {
+    [
+        (A::CATALOG, HandlerKind::Catalog),
+        (A::META, HandlerKind::Meta),
+        (A::STREAM, HandlerKind::Stream),
+        (A::SUBTITLES, HandlerKind::Subtitles),
+        (A::ADDON_CATALOG, HandlerKind::AddonCatalog),
+    ]
+    .into_iter()
+    .filter_map(|(overridden, kind)| overridden.then_some(kind))
+    .collect()
+}
+
+/// Answers a request with the method of `kind`, for the handlers `Builder::from_addon` registers.
+pub(crate) async fn call<A: Addon>(
+    State(addon): State<A>,
+    kind: HandlerKind,
+    path: ResourcePath,
+    ctx: RequestContext,
+) -> HandlerResult {
+    match kind {
+        HandlerKind::Catalog => addon.catalog(&path, &ctx).await,
+        HandlerKind::Meta => addon.meta(&path, &ctx).await,
+        HandlerKind::Stream => addon.stream(&path, &ctx).await,
+        HandlerKind::Subtitles => addon.subtitles(&path, &ctx).await,
+        HandlerKind::AddonCatalog => addon.addon_catalog(&path, &ctx).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::{self, Future};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use http::StatusCode;
+    use stremio_core::types::addon::{
+        Manifest, ManifestCatalog, ManifestExtra, ManifestResource, ResourcePath, ResourceResponse,
+    };
+
+    use crate::addon::{self, Addon};
+    use crate::builder::{Builder, HandlerKind};
+    use crate::context::RequestContext;
+    use crate::error::{HandlerError, HandlerResult, ValidationIssue};
+    use crate::server::ServerOptions;
+    use crate::test_util;
+
+    struct Movies {
+        resources: Vec<ManifestResource>,
+    }
+
+    impl Addon for Movies {
+        const CATALOG: bool = true;
+        const STREAM: bool = true;
+
+        fn manifest(&self) -> Manifest {
+            Manifest {
+                types: vec!["movie".into()],
+                resources: self.resources.clone(),
+                catalogs: vec![ManifestCatalog {
+                    r#type: "movie".into(),
+                    id: "top".into(),
+                    name: None,
+                    extra: ManifestExtra::Full { props: vec![] },
+                }],
+                ..test_util::manifest()
+            }
+        }
+
+        async fn catalog(&self, _: &ResourcePath, _: &RequestContext) -> HandlerResult {
+            Ok(ResourceResponse::Metas { metas: vec![] })
+        }
+
+        async fn stream(&self, path: &ResourcePath, _: &RequestContext) -> HandlerResult {
+            match path.id.as_str() {
+                "tt1" => Ok(ResourceResponse::Streams { streams: vec![] }),
+                _ => Err(HandlerError::NotFound),
+            }
+        }
+    }
+
+    /// Does its work before returning the future, as methods which are not `async fn` can.
+    struct Eager {
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl Eager {
+        fn call(&self) -> impl Future<Output = HandlerResult> + Send {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            future::ready(Ok(ResourceResponse::Streams { streams: vec![] }))
+        }
+    }
+
+    impl Addon for Eager {
+        const STREAM: bool = true;
+
+        fn manifest(&self) -> Manifest {
+            Manifest {
+                types: vec!["movie".into()],
+                ..test_util::manifest()
+            }
+        }
+
+        fn stream(
+            &self,
+            _: &ResourcePath,
+            _: &RequestContext,
+        ) -> impl Future<Output = HandlerResult> + Send {
+            self.call()
+        }
+    }
+
+    #[tokio::test]
+    async fn handlers_are_not_called_when_building() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let router = Builder::from_addon(Eager {
+            calls: calls.clone(),
+        })
+        .build(ServerOptions::default());
+        let calls = || calls.load(Ordering::SeqCst);
+        assert_eq!(calls(), 0);
+        assert_eq!(
+            test_util::status_of(&router, "http://127.0.0.1:7070/meta/movie/tt1.json").await,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            test_util::status_of(&router, "http://127.0.0.1:7070/stream/movie/tt1.json").await,
+            StatusCode::OK
+        );
+        assert_eq!(calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn addon_methods_can_be_called_directly() {
+        let addon = Movies { resources: vec![] };
+        let ctx = RequestContext::default();
+        let found = addon
+            .stream(&ResourcePath::without_extra("stream", "movie", "tt1"), &ctx)
+            .await;
+        assert!(found.is_ok());
+        let missing = addon
+            .stream(&ResourcePath::without_extra("stream", "movie", "tt2"), &ctx)
+            .await;
+        assert!(matches!(missing, Err(HandlerError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn router_serves_overridden_methods() {
+        let router =
+            Builder::from_addon(Movies { resources: vec![] }).build(ServerOptions::default());
+        assert_eq!(
+            test_util::status_of(&router, "http://127.0.0.1:7070/catalog/movie/top.json").await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            test_util::status_of(&router, "http://127.0.0.1:7070/stream/movie/tt1.json").await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            test_util::status_of(&router, "http://127.0.0.1:7070/meta/movie/tt1.json").await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[test]
+    fn declared_resources_without_method_fail_validation() {
+        let addon = Movies {
+            resources: vec![ManifestResource::Short("meta".into())],
+        };
+        let err = Builder::from_addon(addon)
+            .try_build(ServerOptions::default())
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.issues(),
+            [ValidationIssue::MissingHandler {
+                resource: "meta".into()
+            }]
+        );
+    }
+
+    /// Overrides `subtitles` without setting `SUBTITLES`.
+    struct Undeclared {
+        resources: Vec<ManifestResource>,
+    }
+
+    impl Addon for Undeclared {
+        const STREAM: bool = true;
+
+        fn manifest(&self) -> Manifest {
+            Manifest {
+                types: vec!["movie".into()],
+                resources: self.resources.clone(),
+                ..test_util::manifest()
+            }
+        }
+
+        async fn stream(&self, _: &ResourcePath, _: &RequestContext) -> HandlerResult {
+            Ok(ResourceResponse::Streams { streams: vec![] })
+        }
+
+        async fn subtitles(&self, _: &ResourcePath, _: &RequestContext) -> HandlerResult {
+            Ok(ResourceResponse::Subtitles { subtitles: vec![] })
+        }
+    }
+
+    #[test]
+    fn kinds_are_the_ones_whose_constant_is_set() {
+        assert_eq!(
+            addon::kinds::<Movies>(),
+            [HandlerKind::Catalog, HandlerKind::Stream]
+        );
+        assert_eq!(addon::kinds::<Undeclared>(), [HandlerKind::Stream]);
+    }
+
+    #[tokio::test]
+    async fn overridden_methods_without_constant_are_not_served() {
+        let router =
+            Builder::from_addon(Undeclared { resources: vec![] }).build(ServerOptions::default());
+        assert_eq!(
+            test_util::status_of(&router, "http://127.0.0.1:7070/subtitles/movie/tt1.json").await,
+            StatusCode::NOT_FOUND
+        );
+        // the method is reported once the manifest declares its resource
+        let addon = Undeclared {
+            resources: vec![ManifestResource::Short("subtitles".into())],
+        };
+        let err = Builder::from_addon(addon)
+            .try_build(ServerOptions::default())
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.issues(),
+            [ValidationIssue::MissingHandler {
+                resource: "subtitles".into()
+            }]
+        );
+    }
+}
@@ -0,0 +1,323 @@
+use ::axum::async_trait;
+use ::axum::body::Body;
+use ::axum::extract::{FromRef, FromRequestParts};
+use ::axum::response::{IntoResponse, Response};
+use bytes::Bytes;
+use http::request::Parts;
+use stremio_core::types::addon::ResourcePath;
+
+use crate::config::UserConfig;
+use crate::context::RequestContext;
+use crate::error::HandlerResult;
+use crate::extra::{self, FromExtra};
+use crate::parser;
+use crate::router::{self, ResponseKind, Router};
+
+impl Router {
+    /// Serves the addon from an axum app, under any prefix it is nested in. Routes added to it
+    /// take precedence over the addon's handlers, and can use the extractors of this module with
+    /// the router as their state:
+    ///
+    /// ```no_run
+    /// # use axum::extract::State;
+    /// # use axum::response::Response;
+    /// # use axum::routing::get;
+    /// # use stremio_addon_sdk::axum::Resource;
+    /// # use stremio_addon_sdk::builder::{Builder, HandlerKind};
+    /// # use stremio_addon_sdk::context::RequestContext;
+    /// # use stremio_addon_sdk::error::HandlerResult;
+    /// # use stremio_addon_sdk::router::Router;
+    /// # use stremio_addon_sdk::server::ServerOptions;
+    /// # use stremio_addon_sdk::stremio_core::types::addon::{Manifest, ResourcePath};
+    /// # async fn find_streams(path: &ResourcePath) -> HandlerResult { unimplemented!() }
+    /// # fn app(manifest: Manifest, options: ServerOptions) {
+    /// async fn stream(
+    ///     State(addon): State<Router>,
+    ///     Resource(path): Resource,
+    ///     ctx: RequestContext,
+    /// ) -> Response {
+    ///     addon.respond(&path, &ctx, find_streams(&path).await)
+    /// }
+    ///
+    /// let addon = Builder::new(manifest).external(HandlerKind::Stream).build(options);
+    /// let app = axum::Router::new().nest(
+    ///     "/addon",
+    ///     addon.clone().into_axum().route("/stream/*path", get(stream)).with_state(addon),
+    /// );
+    /// # let _: axum::Router = app;
+    /// # }
+    /// ```
+    pub fn into_axum<S>(self) -> ::axum::Router<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        ::axum::Router::new().fallback_service(self)
+    }
+
+    /// Answers with what an axum handler found for a resource, with the same checks and headers
+    /// as the answers of the addon's handlers.
+    pub fn respond(
+        &self,
+        path: &ResourcePath,
+        ctx: &RequestContext,
+        result: HandlerResult,
+    ) -> Response {
+        let response = self.response_to(path, ctx, result);
+        into_response(router::or_internal_error(
+            response,
+            format_args!("{:?} (request {})", path, ctx.request_id()),
+        ))
+    }
+}
+
+/// Resource path of the request, checked against the manifest like the paths the addon's
+/// handlers receive. Paths of undeclared resources are rejected with a 404.
+#[derive(Debug, Clone)]
+pub struct Resource(pub ResourcePath);
+
+/// Extra of the request's resource path.
+#[derive(Debug, Clone)]
+pub struct Extra<T>(pub T);
+
+/// Rejection of the extractors, answered like the addon answers the same invalid requests.
+#[derive(Debug)]
+pub struct Rejection(Box<http::Response<Bytes>>);
+
+impl Rejection {
+    fn new(router: &Router, parts: &Parts, kind: ResponseKind) -> Self {
+        let response = router::or_internal_error(router.response_from(kind), &parts.uri);
+        Self(Box::new(response))
+    }
+}
+
+impl IntoResponse for Rejection {
+    fn into_response(self) -> Response {
+        into_response(*self.0)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Resource
+where
+    Router: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Rejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let router = Router::from_ref(state);
+        let (_, path) = router.split_config(parts.uri.path());
+        let path = parser::parse_resource_path(path).map_err(|err| {
+            Rejection::new(&router, parts, ResponseKind::BadRequest(err.to_string()))
+        })?;
+        if !router.is_supported(&path) {
+            return Err(Rejection::new(&router, parts, ResponseKind::NotFound));
+        }
+        if let Some(catalog) = router.declared_catalog(&path) {
+            extra::check_declared(catalog, &path.extra).map_err(|reason| {
+                Rejection::new(&router, parts, ResponseKind::BadRequest(reason))
+            })?;
+        }
+        Ok(Resource(path))
+    }
+}
+
+#[async_trait]
+impl<S, T> FromRequestParts<S> for Extra<T>
+where
+    Router: FromRef<S>,
+    S: Send + Sync,
+    T: FromExtra,
+{
+    type Rejection = Rejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Resource(path) = Resource::from_request_parts(parts, state).await?;
+        T::from_path(&path).map(Extra).map_err(|err| {
+            let router = Router::from_ref(state);
+            Rejection::new(&router, parts, ResponseKind::Error(err.into()))
+        })
+    }
+}
+
+/// User config of a configured install, rejected with a 400 when it is missing or not UTF-8.
+/// Extract an `Option<UserConfig>` when the addon also works without one.
+#[async_trait]
+impl<S> FromRequestParts<S> for UserConfig
+where
+    Router: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Rejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let router = Router::from_ref(state);
+        user_config(&router, parts)?.ok_or_else(|| {
+            Rejection::new(
+                &router,
+                parts,
+                ResponseKind::BadRequest("missing user config".into()),
+            )
+        })
+    }
+}
+
+/// Context of the request, as the addon's handlers receive it.
+#[async_trait]
+impl<S> FromRequestParts<S> for RequestContext
+where
+    Router: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Rejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let router = Router::from_ref(state);
+        let config = user_config(&router, parts)?;
+        Ok(RequestContext::from_parts(parts, config, router.options()))
+    }
+}
+
+fn user_config(router: &Router, parts: &Parts) -> Result<Option<UserConfig>, Rejection> {
+    match router.split_config(parts.uri.path()).0 {
+        Some(segment) => UserConfig::from_segment(segment).map(Some).ok_or_else(|| {
+            Rejection::new(
+                router,
+                parts,
+                ResponseKind::BadRequest("invalid user config".into()),
+            )
+        }),
+        None => Ok(None),
+    }
+}
+
+fn into_response(response: http::Response<Bytes>) -> Response {
+    response.map(Body::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use ::axum::body::{self, Body};
+    use ::axum::extract::State;
+    use ::axum::response::Response;
+    use ::axum::routing::get;
+    use http::{header, Request, StatusCode};
+    use stremio_core::types::addon::{
+        Manifest, ManifestCatalog, ManifestExtra, ResourcePath, ResourceResponse,
+    };
+    use stremio_core::types::resource::Stream;
+    use tower::ServiceExt;
+
+    use crate::axum::{Extra, Resource};
+    use crate::builder::{Builder, HandlerKind};
+    use crate::config::UserConfig;
+    use crate::context::RequestContext;
+    use crate::extra::CatalogExtra;
+    use crate::router::Router;
+    use crate::server::ServerOptions;
+    use crate::test_util;
+
+    /// Streams are answered by the addon, the catalog by the axum routes of `app`.
+    fn addon() -> Router {
+        let manifest = Manifest {
+            catalogs: vec![ManifestCatalog {
+                r#type: "movie".into(),
+                id: "top".into(),
+                name: None,
+                extra: ManifestExtra::Short {
+                    required: vec![],
+                    supported: vec!["search".into()],
+                },
+            }],
+            ..test_util::stream_manifest()
+        };
+        Builder::new(manifest)
+            .stream(|_: &ResourcePath| Vec::<Stream>::new())
+            .external(HandlerKind::Catalog)
+            .build(ServerOptions::default())
+    }
+
+    async fn catalog(
+        State(addon): State<Router>,
+        Resource(path): Resource,
+        ctx: RequestContext,
+    ) -> Response {
+        addon.respond(&path, &ctx, Ok(ResourceResponse::Metas { metas: vec![] }))
+    }
+
+    async fn configured_catalog(config: UserConfig, Extra(extra): Extra<CatalogExtra>) -> String {
+        format!("{} {:?}", config.raw(), extra.search)
+    }
+
+    fn app() -> ::axum::Router {
+        let addon = addon();
+        let routes = addon
+            .clone()
+            .into_axum()
+            .route("/catalog/*path", get(catalog))
+            .route("/:config/catalog/*path", get(configured_catalog))
+            .with_state(addon);
+        ::axum::Router::new().nest("/addon", routes)
+    }
+
+    async fn get_response(uri: &str) -> (StatusCode, Option<String>, String) {
+        let response = app()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let cache_control = response
+            .headers()
+            .get(header::CACHE_CONTROL)
+            .map(|value| value.to_str().unwrap().to_owned());
+        let status = response.status();
+        let body = body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            cache_control,
+            String::from_utf8(body.to_vec()).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn addon_is_served_under_the_nesting_prefix() {
+        let (status, _, _) = get_response("/addon/manifest.json").await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _, body) = get_response("/addon/stream/movie/tt1.json").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, r#"{"streams":[]}"#);
+        let (status, _, _) = get_response("/stream/movie/tt1.json").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn axum_handlers_answer_like_the_addon() {
+        let (status, cache_control, body) = get_response("/addon/catalog/movie/top.json").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(cache_control.as_deref(), Some("max-age=259200, public"));
+        assert_eq!(body, r#"{"metas":[]}"#);
+    }
+
+    #[tokio::test]
+    async fn extractors_reject_like_the_addon() {
+        let (status, _, _) = get_response("/addon/catalog/movie/popular.json").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _, body) = get_response("/addon/catalog/movie/top/genre=Drama.json").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.starts_with(r#"{"error":"bad request: "#), "{}", body);
+        let (status, _, body) = get_response("/addon/%FF/catalog/movie/top.json").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, r#"{"error":"bad request: invalid user config"}"#);
+    }
+
+    #[tokio::test]
+    async fn extractors_read_config_and_extra() {
+        let (status, _, body) = get_response(
+            "/addon/%7B%22region%22%3A%22de%22%7D/catalog/movie/top/search=office.json",
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, r#"{"region":"de"} Some("office")"#);
+    }
+}
@@ -5,25 +5,85 @@ use futures::future::BoxFuture;
 use stremio_core::constants::{
     CATALOG_RESOURCE_NAME, META_RESOURCE_NAME, STREAM_RESOURCE_NAME, SUBTITLES_RESOURCE_NAME,
 };
-use stremio_core::types::addon::{Manifest, ManifestResource, ResourcePath, ResourceResponse};
+use stremio_core::types::addon::{
+    DescriptorPreview, Manifest, ManifestCatalog, ManifestResource, ResourcePath, ResourceResponse,
+};
+use stremio_core::types::resource::{MetaItem, MetaItemPreview, Stream, Subtitles};
 
+use crate::addon::{self, Addon};
+use crate::context::RequestContext;
+use crate::error::{BuildError, HandlerError, HandlerResult, Severity, ValidationIssue};
+use crate::handler::IntoHandler;
+use crate::manifest::validate_manifest;
+use crate::middleware::Middleware;
 use crate::router::Router;
 use crate::server::ServerOptions;
+use crate::state::State;
+use crate::utils;
+
+pub(crate) type HandlerFn = dyn for<'a> Fn(&'a ResourcePath, &'a RequestContext) -> BoxFuture<'a, HandlerResult>
+    + Send
+    + Sync
+    + 'static;
 
-type HandlerFn =
-dyn Fn(&ResourcePath) -> BoxFuture<Option<ResourceResponse>> + Send + Sync + 'static;
+/// Resource name of addon catalogs; stremio-core has no constant for it.
+pub(crate) const ADDON_CATALOG_RESOURCE_NAME: &str = "addon_catalog";
 
 #[derive(Clone)]
 pub struct Handler {
     pub(crate) name: String,
+    pub(crate) r#type: Option<String>,
+    pub(crate) id_prefix: Option<String>,
+    pub(crate) id: Option<String>,
     pub(crate) func: Arc<HandlerFn>,
 }
 
+impl Handler {
+    pub(crate) fn matches(&self, path: &ResourcePath) -> bool {
+        self.name == path.resource
+            && self
+                .r#type
+                .as_ref()
+                .map_or(true, |r#type| *r#type == path.r#type)
+            && self
+                .id_prefix
+                .as_ref()
+                .map_or(true, |prefix| path.id.starts_with(prefix.as_str()))
+            && self.id.as_ref().map_or(true, |id| *id == path.id)
+    }
+
+    /// Orders matching handlers so that the most specific binding wins.
+    pub(crate) fn specificity(&self) -> (bool, bool, Option<usize>) {
+        (
+            self.id.is_some(),
+            self.r#type.is_some(),
+            self.id_prefix.as_ref().map(|prefix| prefix.len()),
+        )
+    }
+
+    fn binding(&self) -> String {
+        [
+            Some(&self.name),
+            self.r#type.as_ref(),
+            self.id_prefix.as_ref(),
+            self.id.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join("/")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum HandlerKind {
     Meta,
     Subtitles,
     Stream,
     Catalog,
+    /// Lists other addons; the handler should respond with `ResourceResponse::Addons`.
+    AddonCatalog,
 }
 
 impl Display for HandlerKind {
@@ -33,15 +93,81 @@ impl Display for HandlerKind {
             HandlerKind::Subtitles => SUBTITLES_RESOURCE_NAME,
             HandlerKind::Stream => STREAM_RESOURCE_NAME,
             HandlerKind::Catalog => CATALOG_RESOURCE_NAME,
+            HandlerKind::AddonCatalog => ADDON_CATALOG_RESOURCE_NAME,
         }
-            .to_string();
+        .to_string();
         write!(f, "{}", str)
     }
 }
 
-pub struct Builder {
+/// Selects the requests of a resource that a handler answers: all of them, only those of one
+/// content type, or only those of one content type whose id starts with a prefix.
+///
+/// ```no_run
+/// # use stremio_addon_sdk::builder::{Builder, HandlerKind};
+/// # use stremio_addon_sdk::error::HandlerResult;
+/// # use stremio_addon_sdk::stremio_core::types::addon::{Manifest, ResourcePath};
+/// # async fn handle_streams(path: ResourcePath) -> HandlerResult { unimplemented!() }
+/// # async fn handle_series_streams(path: ResourcePath) -> HandlerResult { unimplemented!() }
+/// # async fn handle_imdb_movie_streams(path: ResourcePath) -> HandlerResult { unimplemented!() }
+/// # fn builder(manifest: Manifest) -> Builder {
+/// Builder::new(manifest)
+///     .handler(HandlerKind::Stream, handle_streams)
+///     .handler((HandlerKind::Stream, "series"), handle_series_streams)
+///     .handler((HandlerKind::Stream, "movie", "tt"), handle_imdb_movie_streams)
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Binding {
+    kind: HandlerKind,
+    r#type: Option<String>,
+    id_prefix: Option<String>,
+    id: Option<String>,
+}
+
+impl From<HandlerKind> for Binding {
+    fn from(kind: HandlerKind) -> Self {
+        Self {
+            kind,
+            r#type: None,
+            id_prefix: None,
+            id: None,
+        }
+    }
+}
+
+impl From<(HandlerKind, &str)> for Binding {
+    fn from((kind, r#type): (HandlerKind, &str)) -> Self {
+        Self {
+            kind,
+            r#type: Some(r#type.into()),
+            id_prefix: None,
+            id: None,
+        }
+    }
+}
+
+impl From<(HandlerKind, &str, &str)> for Binding {
+    fn from((kind, r#type, id_prefix): (HandlerKind, &str, &str)) -> Self {
+        Self {
+            kind,
+            r#type: Some(r#type.into()),
+            id_prefix: Some(id_prefix.into()),
+            id: None,
+        }
+    }
+}
+
+pub struct Builder<S = ()> {
     manifest: Manifest,
     handlers: Vec<Handler>,
+    // (type, id) of the catalogs declared through `Builder::catalog`
+    catalogs: Vec<(String, String)>,
+    duplicates: Vec<String>,
+    // resources answered outside of the router, see `Builder::external`
+    external: Vec<String>,
+    middlewares: Vec<Arc<dyn Middleware>>,
+    state: Arc<S>,
 }
 
 impl Builder {
@@ -49,104 +175,382 @@ impl Builder {
         Self {
             manifest,
             handlers: vec![],
+            catalogs: vec![],
+            duplicates: vec![],
+            external: vec![],
+            middlewares: vec![],
+            state: Arc::new(()),
         }
     }
 
-    pub fn handler<F>(mut self, kind: HandlerKind, handler: F) -> Self
-        where
-            F: Fn(&ResourcePath) -> BoxFuture<Option<ResourceResponse>> + Send + Sync + 'static,
+    /// Builder for an addon implemented as a type. The methods it overrides, as told by its
+    /// constants, become the handlers, and the addon itself is the state they share.
+    pub fn from_addon<A: Addon>(addon: A) -> Builder<A> {
+        let kinds = addon::kinds::<A>();
+        let mut manifest = addon.manifest();
+        for kind in &kinds {
+            let name = kind.to_string();
+            let declared = manifest
+                .resources
+                .iter()
+                .any(|resource| utils::resource_name(resource) == name);
+            if matches!(
+                kind,
+                HandlerKind::Meta | HandlerKind::Stream | HandlerKind::Subtitles
+            ) && !declared
+            {
+                manifest.resources.push(ManifestResource::Short(name));
+            }
+        }
+        kinds
+            .into_iter()
+            .fold(Builder::new(manifest).with_state(addon), |builder, kind| {
+                builder.handler(
+                    kind,
+                    move |state: State<A>, path: ResourcePath, ctx: RequestContext| {
+                        addon::call(state, kind, path, ctx)
+                    },
+                )
+            })
+    }
+
+    /// Sets the state shared by every request, which handlers taking a [`State<S>`] first
+    /// receive.
+    pub fn with_state<S>(self, state: S) -> Builder<S>
+    where
+        S: Send + Sync + 'static,
     {
-        if self.handlers.iter().any(|h| h.name == kind.to_string()) {
-            panic!("handler for resource '{}' is already defined!", kind);
+        Builder {
+            manifest: self.manifest,
+            handlers: self.handlers,
+            catalogs: self.catalogs,
+            duplicates: self.duplicates,
+            external: self.external,
+            middlewares: self.middlewares,
+            state: Arc::new(state),
         }
-        self.handlers.push(Handler {
-            name: kind.to_string(),
-            func: Arc::new(handler),
+    }
+}
+
+impl<S> Builder<S>
+where
+    S: Send + Sync + 'static,
+{
+    /// Registers the handler for a resource, or for part of it (see [`Binding`]). It receives
+    /// the requested path and, depending on its form (see [`IntoHandler`]), the
+    /// [`RequestContext`] of the request and the state.
+    pub fn handler<B, H, M>(mut self, binding: B, handler: H) -> Self
+    where
+        B: Into<Binding>,
+        H: IntoHandler<M, S>,
+    {
+        let func = handler.into_handler(self.state.clone(), Ok);
+        self.push_handler(binding.into(), func);
+        self
+    }
+
+    /// Registers the handler of the `meta` resource. It answers with the meta item, or `None`
+    /// when the item is not found.
+    pub fn meta<H, M>(self, handler: H) -> Self
+    where
+        H: IntoHandler<M, S, Option<MetaItem>>,
+    {
+        self.typed_handler(HandlerKind::Meta, handler, |meta| {
+            meta.map(|meta| ResourceResponse::Meta { meta })
+                .ok_or(HandlerError::NotFound)
+        })
+    }
+
+    /// Registers the handler of the `stream` resource.
+    pub fn stream<H, M>(self, handler: H) -> Self
+    where
+        H: IntoHandler<M, S, Vec<Stream>>,
+    {
+        self.typed_handler(HandlerKind::Stream, handler, |streams| {
+            Ok(ResourceResponse::Streams { streams })
+        })
+    }
+
+    /// Registers the handler of the `subtitles` resource.
+    pub fn subtitles<H, M>(self, handler: H) -> Self
+    where
+        H: IntoHandler<M, S, Vec<Subtitles>>,
+    {
+        self.typed_handler(HandlerKind::Subtitles, handler, |subtitles| {
+            Ok(ResourceResponse::Subtitles { subtitles })
+        })
+    }
+
+    /// Registers the handler of the addon catalogs.
+    pub fn addon_catalog<H, M>(self, handler: H) -> Self
+    where
+        H: IntoHandler<M, S, Vec<DescriptorPreview>>,
+    {
+        self.typed_handler(HandlerKind::AddonCatalog, handler, |addons| {
+            Ok(ResourceResponse::Addons { addons })
+        })
+    }
+
+    fn typed_handler<H, M, T>(
+        mut self,
+        kind: HandlerKind,
+        handler: H,
+        respond: fn(T) -> HandlerResult,
+    ) -> Self
+    where
+        H: IntoHandler<M, S, T>,
+    {
+        let func = handler.into_handler(self.state.clone(), respond);
+        self.push_handler(kind.into(), func);
+        self
+    }
+
+    /// Declares a catalog in `manifest.catalogs` and registers the handler that serves it,
+    /// so that `/catalog/{type}/{id}` requests for it are dispatched straight to `handler`.
+    /// The handler answers with the items of the catalog.
+    pub fn catalog<H, M>(mut self, catalog: ManifestCatalog, handler: H) -> Self
+    where
+        H: IntoHandler<M, S, Vec<MetaItemPreview>>,
+    {
+        let func = handler.into_handler(self.state.clone(), |metas| {
+            Ok(ResourceResponse::Metas { metas })
         });
+        self.push_catalog(catalog, func);
+        self
+    }
+
+    /// Adds a middleware running around every handler, inside the ones added before it.
+    pub fn middleware<M>(mut self, middleware: M) -> Self
+    where
+        M: Middleware,
+    {
+        self.middlewares.push(Arc::new(middleware));
         self
     }
 
+    /// Declares a resource answered outside of the router, e.g. by a route of the axum app it is
+    /// mounted in, so that validation does not expect a handler for it.
+    pub fn external(mut self, kind: HandlerKind) -> Self {
+        self.external.push(kind.to_string());
+        self
+    }
+
+    fn push_catalog(&mut self, catalog: ManifestCatalog, func: Arc<HandlerFn>) {
+        let binding = Binding {
+            kind: HandlerKind::Catalog,
+            r#type: Some(catalog.r#type.clone()),
+            id_prefix: None,
+            id: Some(catalog.id.clone()),
+        };
+        self.push_handler(binding, func);
+        self.catalogs
+            .push((catalog.r#type.clone(), catalog.id.clone()));
+        self.manifest.catalogs.push(catalog);
+    }
+
+    fn push_handler(&mut self, binding: Binding, func: Arc<HandlerFn>) {
+        let handler = Handler {
+            name: binding.kind.to_string(),
+            r#type: binding.r#type,
+            id_prefix: binding.id_prefix,
+            id: binding.id,
+            func,
+        };
+        if self.handlers.iter().any(|h| {
+            h.name == handler.name
+                && h.r#type == handler.r#type
+                && h.id_prefix == handler.id_prefix
+                && h.id == handler.id
+        }) {
+            self.duplicates.push(handler.binding());
+            return;
+        }
+        self.handlers.push(handler);
+    }
+
+    /// Builds the router, panicking when validation finds an error. Warnings are logged.
     pub fn build(self, options: ServerOptions) -> Router {
-        self.validate();
-        Router::new(self.manifest, self.handlers, options)
+        match self.try_build(options) {
+            Ok(router) => {
+                for warning in router.warnings() {
+                    log::warn!("{}", warning);
+                }
+                router
+            }
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Builds the router, or returns every issue found when at least one of them is an error.
+    /// The warnings of a router that is built are kept in `Router::warnings`.
+    pub fn try_build(self, options: ServerOptions) -> Result<Router, BuildError> {
+        let issues = self.validate();
+        if issues
+            .iter()
+            .any(|issue| issue.severity() == Severity::Error)
+        {
+            return Err(BuildError::new(issues));
+        }
+        Ok(Router::new(self.manifest, self.handlers, options)
+            .with_middlewares(self.middlewares)
+            .with_warnings(issues))
     }
 
-    fn validate(&self) {
+    /// Validates the manifest and cross-checks it with the registered handlers.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
         let mut errors = Vec::new();
         let mut handler_names = Vec::new();
         let manifest = &self.manifest;
 
-        if self.handlers.is_empty() {
-            errors.push("at least one handler must be defined".into());
+        errors.extend(validate_manifest(manifest));
+        if self.handlers.is_empty() && self.external.is_empty() {
+            errors.push(ValidationIssue::NoHandlers);
+        }
+        for binding in &self.duplicates {
+            errors.push(ValidationIssue::DuplicateHandler {
+                binding: binding.clone(),
+            });
         }
         // get all handlers that are declared in the manifest
         if !manifest.catalogs.is_empty() {
             handler_names.push(HandlerKind::Catalog.to_string());
         }
+        if !manifest.addon_catalogs.is_empty() {
+            handler_names.push(HandlerKind::AddonCatalog.to_string());
+        }
         for resource in &manifest.resources {
-            // NOTE: resource.name() should probably be public in stremio-core, making this code unnecessary
-            match resource {
-                ManifestResource::Short(name) => handler_names.push(name.to_string()),
-                ManifestResource::Full { name, .. } => handler_names.push(name.to_string()),
-            }
+            handler_names.push(utils::resource_name(resource).to_string());
         }
         // check if defined handlers are also specified in the manifest
         for handler in &self.handlers {
-            if !handler_names.iter().any(|name| *name == handler.name) {
-                if handler.name == HandlerKind::Catalog.to_string() {
-                    errors.push(
-                        "manifest.catalogs is empty, 'catalog' handler will never be called"
-                            .to_string(),
-                    );
+            if !handler_names.contains(&handler.name) {
+                if handler.name == HandlerKind::Catalog.to_string()
+                    || handler.name == HandlerKind::AddonCatalog.to_string()
+                {
+                    errors.push(ValidationIssue::EmptyCatalogs {
+                        resource: handler.name.clone(),
+                    });
                 } else {
-                    errors.push(format!(
-                        "manifest.resources does not contain: {}",
-                        handler.name
-                    ));
+                    errors.push(ValidationIssue::UnusedHandler {
+                        binding: handler.binding(),
+                    });
                 }
             }
         }
         // check if handlers that are specified in the manifest are also defined
-        for name in handler_names {
-            if !self.handlers.iter().any(|handler| name == handler.name) {
-                errors.push(format!(
-                    "manifest definition requires handler for '{}', but it is not provided",
-                    name
-                ));
+        for name in &handler_names {
+            if !self.handlers.iter().any(|handler| *name == handler.name)
+                && !self.external.contains(name)
+            {
+                errors.push(ValidationIssue::MissingHandler {
+                    resource: name.clone(),
+                });
             }
         }
-        if !errors.is_empty() {
-            let error = errors.join("\n");
-            let error_formatted = format!("\n--failed to build addon interface-- \n{}", error);
-            panic!("{}", error_formatted);
+        // check if type and id prefix bindings can be reached with what the manifest declares
+        for handler in &self.handlers {
+            let Some(r#type) = &handler.r#type else {
+                continue;
+            };
+            if !handler_names.contains(&handler.name) {
+                continue;
+            }
+            let (types, id_prefixes) = if handler.name == HandlerKind::Catalog.to_string() {
+                let types = manifest.catalogs.iter().map(|catalog| &catalog.r#type);
+                (types.collect::<Vec<_>>(), None)
+            } else if handler.name == HandlerKind::AddonCatalog.to_string() {
+                let types = manifest
+                    .addon_catalogs
+                    .iter()
+                    .map(|catalog| &catalog.r#type);
+                (types.collect::<Vec<_>>(), None)
+            } else {
+                let (types, id_prefixes) = utils::resource_declaration(manifest, &handler.name)
+                    .expect("resource is declared in the manifest");
+                (types.iter().collect::<Vec<_>>(), id_prefixes)
+            };
+            if !types.contains(&r#type) {
+                errors.push(ValidationIssue::UndeclaredType {
+                    binding: handler.binding(),
+                    r#type: r#type.clone(),
+                });
+            }
+            if let (Some(prefix), Some(id_prefixes)) = (&handler.id_prefix, id_prefixes) {
+                if !id_prefixes.iter().any(|declared| {
+                    prefix.starts_with(declared.as_str()) || declared.starts_with(prefix.as_str())
+                }) {
+                    errors.push(ValidationIssue::UndeclaredIdPrefix {
+                        binding: handler.binding(),
+                        id_prefix: prefix.clone(),
+                    });
+                }
+            }
         }
+        // once catalogs have their own handlers, every other catalog needs one as well
+        if !self.catalogs.is_empty() {
+            for catalog in &manifest.catalogs {
+                let path = ResourcePath::without_extra(
+                    CATALOG_RESOURCE_NAME,
+                    &catalog.r#type,
+                    &catalog.id,
+                );
+                if !self.handlers.iter().any(|handler| handler.matches(&path)) {
+                    errors.push(ValidationIssue::MissingHandler {
+                        resource: format!(
+                            "{}/{}/{}",
+                            HandlerKind::Catalog,
+                            catalog.r#type,
+                            catalog.id
+                        ),
+                    });
+                }
+            }
+        }
+        errors
     }
 }
 
+pub(crate) fn handler_fn<F>(handler: F) -> Arc<HandlerFn>
+where
+    F: for<'a> Fn(&'a ResourcePath, &'a RequestContext) -> BoxFuture<'a, HandlerResult>
+        + Send
+        + Sync
+        + 'static,
+{
+    Arc::new(handler)
+}
+
 #[cfg(test)]
 mod tests {
     use futures::future;
-    use stremio_core::types::addon::{Manifest, ManifestResource, ResourceResponse};
+    use stremio_core::types::addon::{
+        Manifest, ManifestCatalog, ManifestExtra, ManifestResource, ResourcePath, ResourceResponse,
+    };
 
     use crate::builder::{Builder, HandlerKind};
+    use crate::context::RequestContext;
+    use crate::error::{Severity, ValidationIssue};
     use crate::server::ServerOptions;
-    use crate::utils;
+    use crate::test_util;
 
     #[test]
     #[should_panic]
     fn builder_panics_if_no_handlers_attached() {
-        Builder::new(utils::default_manifest()).build(ServerOptions::default());
+        Builder::new(test_util::manifest()).build(ServerOptions::default());
     }
 
     #[test]
     #[should_panic]
     fn builder_panics_if_no_resources_defined_for_handler() {
-        Builder::new(utils::default_manifest())
-            .handler(HandlerKind::Stream, |_| {
-                Box::pin(future::ready(Some(ResourceResponse::Streams {
-                    streams: vec![],
-                })))
-            })
+        Builder::new(test_util::manifest())
+            .handler(
+                HandlerKind::Stream,
+                |_: &ResourcePath, _: &RequestContext| {
+                    Box::pin(future::ready(Ok(ResourceResponse::Streams {
+                        streams: vec![],
+                    })))
+                },
+            )
             .build(ServerOptions::default());
     }
 
@@ -158,31 +562,342 @@ mod tests {
                 ManifestResource::Short("meta".into()),
                 ManifestResource::Short("stream".into()),
             ],
-            ..utils::default_manifest()
+            ..test_util::manifest()
         };
         Builder::new(manifest)
-            .handler(HandlerKind::Stream, |_| {
-                Box::pin(future::ready(Some(ResourceResponse::Streams {
-                    streams: vec![],
-                })))
-            })
+            .handler(
+                HandlerKind::Stream,
+                |_: &ResourcePath, _: &RequestContext| {
+                    Box::pin(future::ready(Ok(ResourceResponse::Streams {
+                        streams: vec![],
+                    })))
+                },
+            )
             .build(ServerOptions::default());
     }
 
     #[test]
     #[should_panic]
     fn builder_panics_if_handler_is_redefined() {
-        Builder::new(utils::default_manifest())
-            .handler(HandlerKind::Subtitles, |_| {
-                Box::pin(future::ready(Some(ResourceResponse::Subtitles {
-                    subtitles: vec![],
-                })))
-            })
-            .handler(HandlerKind::Subtitles, |_| {
-                Box::pin(future::ready(Some(ResourceResponse::Subtitles {
-                    subtitles: vec![],
-                })))
-            })
+        Builder::new(test_util::manifest())
+            .handler(
+                HandlerKind::Subtitles,
+                |_: &ResourcePath, _: &RequestContext| {
+                    Box::pin(future::ready(Ok(ResourceResponse::Subtitles {
+                        subtitles: vec![],
+                    })))
+                },
+            )
+            .handler(
+                HandlerKind::Subtitles,
+                |_: &ResourcePath, _: &RequestContext| {
+                    Box::pin(future::ready(Ok(ResourceResponse::Subtitles {
+                        subtitles: vec![],
+                    })))
+                },
+            )
+            .build(ServerOptions::default());
+    }
+
+    #[test]
+    #[should_panic]
+    fn builder_panics_if_no_handler_defined_for_addon_catalogs() {
+        let manifest = Manifest {
+            resources: vec![ManifestResource::Short("stream".into())],
+            addon_catalogs: vec![ManifestCatalog {
+                r#type: "other".into(),
+                id: "community".into(),
+                name: Some("Community".into()),
+                extra: ManifestExtra::Full { props: vec![] },
+            }],
+            ..test_util::manifest()
+        };
+        Builder::new(manifest)
+            .handler(
+                HandlerKind::Stream,
+                |_: &ResourcePath, _: &RequestContext| {
+                    Box::pin(future::ready(Ok(ResourceResponse::Streams {
+                        streams: vec![],
+                    })))
+                },
+            )
+            .build(ServerOptions::default());
+    }
+
+    #[test]
+    #[should_panic]
+    fn builder_panics_if_addon_catalogs_empty_for_handler() {
+        Builder::new(test_util::manifest())
+            .handler(
+                HandlerKind::AddonCatalog,
+                |_: &ResourcePath, _: &RequestContext| {
+                    Box::pin(future::ready(Ok(ResourceResponse::Addons {
+                        addons: vec![],
+                    })))
+                },
+            )
+            .build(ServerOptions::default());
+    }
+
+    #[test]
+    fn builder_accepts_addon_catalog_handler() {
+        let manifest = Manifest {
+            addon_catalogs: vec![ManifestCatalog {
+                r#type: "other".into(),
+                id: "community".into(),
+                name: Some("Community".into()),
+                extra: ManifestExtra::Full { props: vec![] },
+            }],
+            ..test_util::manifest()
+        };
+        Builder::new(manifest)
+            .handler(
+                HandlerKind::AddonCatalog,
+                |_: &ResourcePath, _: &RequestContext| {
+                    Box::pin(future::ready(Ok(ResourceResponse::Addons {
+                        addons: vec![],
+                    })))
+                },
+            )
+            .build(ServerOptions::default());
+    }
+
+    fn streams_manifest() -> Manifest {
+        Manifest {
+            types: vec!["movie".into(), "series".into()],
+            resources: vec![ManifestResource::Full {
+                name: "stream".into(),
+                types: None,
+                id_prefixes: Some(vec!["tt".into(), "kitsu:".into()]),
+            }],
+            ..test_util::manifest()
+        }
+    }
+
+    #[test]
+    fn builder_accepts_handlers_bound_to_types_and_id_prefixes() {
+        Builder::new(streams_manifest())
+            .handler(
+                HandlerKind::Stream,
+                |_: &ResourcePath, _: &RequestContext| {
+                    Box::pin(future::ready(Ok(ResourceResponse::Streams {
+                        streams: vec![],
+                    })))
+                },
+            )
+            .handler(
+                (HandlerKind::Stream, "series"),
+                |_: &ResourcePath, _: &RequestContext| {
+                    Box::pin(future::ready(Ok(ResourceResponse::Streams {
+                        streams: vec![],
+                    })))
+                },
+            )
+            .handler(
+                (HandlerKind::Stream, "series", "kitsu:"),
+                |_: &ResourcePath, _: &RequestContext| {
+                    Box::pin(future::ready(Ok(ResourceResponse::Streams {
+                        streams: vec![],
+                    })))
+                },
+            )
+            .build(ServerOptions::default());
+    }
+
+    #[test]
+    fn builder_warns_if_handlers_bound_to_undeclared_type_or_id_prefix() {
+        let builder = Builder::new(streams_manifest())
+            .handler(
+                HandlerKind::Stream,
+                |_: &ResourcePath, _: &RequestContext| {
+                    Box::pin(future::ready(Ok(ResourceResponse::Streams {
+                        streams: vec![],
+                    })))
+                },
+            )
+            .handler(
+                (HandlerKind::Stream, "channel"),
+                |_: &ResourcePath, _: &RequestContext| {
+                    Box::pin(future::ready(Ok(ResourceResponse::Streams {
+                        streams: vec![],
+                    })))
+                },
+            )
+            .handler(
+                (HandlerKind::Stream, "movie", "yt"),
+                |_: &ResourcePath, _: &RequestContext| {
+                    Box::pin(future::ready(Ok(ResourceResponse::Streams {
+                        streams: vec![],
+                    })))
+                },
+            );
+        let router = builder.try_build(ServerOptions::default()).unwrap();
+        let issues = router.warnings();
+        assert_eq!(
+            issues,
+            [
+                ValidationIssue::UndeclaredType {
+                    binding: "stream/channel".into(),
+                    r#type: "channel".into()
+                },
+                ValidationIssue::UndeclaredIdPrefix {
+                    binding: "stream/movie/yt".into(),
+                    id_prefix: "yt".into()
+                },
+            ]
+        );
+        assert!(issues
+            .iter()
+            .all(|issue| issue.severity() == Severity::Warning));
+    }
+
+    #[test]
+    #[should_panic]
+    fn builder_panics_if_binding_is_redefined() {
+        Builder::new(streams_manifest())
+            .handler(
+                (HandlerKind::Stream, "movie"),
+                |_: &ResourcePath, _: &RequestContext| {
+                    Box::pin(future::ready(Ok(ResourceResponse::Streams {
+                        streams: vec![],
+                    })))
+                },
+            )
+            .handler(
+                (HandlerKind::Stream, "movie"),
+                |_: &ResourcePath, _: &RequestContext| {
+                    Box::pin(future::ready(Ok(ResourceResponse::Streams {
+                        streams: vec![],
+                    })))
+                },
+            )
             .build(ServerOptions::default());
     }
+
+    fn catalog(r#type: &str, id: &str) -> ManifestCatalog {
+        ManifestCatalog {
+            r#type: r#type.into(),
+            id: id.into(),
+            name: Some(id.into()),
+            extra: ManifestExtra::Full { props: vec![] },
+        }
+    }
+
+    #[test]
+    fn builder_declares_catalogs_with_handlers() {
+        let manifest = Manifest {
+            types: vec!["movie".into(), "series".into()],
+            ..test_util::manifest()
+        };
+        let builder = Builder::new(manifest)
+            .catalog(catalog("movie", "top"), |_: &ResourcePath| Ok(vec![]))
+            .catalog(catalog("series", "top"), |_: &ResourcePath| Ok(vec![]));
+        assert_eq!(
+            builder.manifest.catalogs,
+            vec![catalog("movie", "top"), catalog("series", "top")]
+        );
+        builder.build(ServerOptions::default());
+    }
+
+    #[test]
+    #[should_panic]
+    fn builder_panics_if_catalog_type_not_declared() {
+        let manifest = Manifest {
+            types: vec!["movie".into()],
+            ..test_util::manifest()
+        };
+        Builder::new(manifest)
+            .catalog(catalog("series", "top"), |_: &ResourcePath| Ok(vec![]))
+            .build(ServerOptions::default());
+    }
+
+    #[test]
+    #[should_panic]
+    fn builder_panics_if_catalog_declared_twice() {
+        let manifest = Manifest {
+            types: vec!["movie".into()],
+            catalogs: vec![catalog("movie", "top")],
+            ..test_util::manifest()
+        };
+        Builder::new(manifest)
+            .catalog(catalog("movie", "top"), |_: &ResourcePath| Ok(vec![]))
+            .build(ServerOptions::default());
+    }
+
+    #[test]
+    #[should_panic]
+    fn builder_panics_if_declared_catalog_has_no_handler() {
+        let manifest = Manifest {
+            types: vec!["movie".into()],
+            catalogs: vec![catalog("movie", "popular")],
+            ..test_util::manifest()
+        };
+        Builder::new(manifest)
+            .catalog(catalog("movie", "top"), |_: &ResourcePath| Ok(vec![]))
+            .build(ServerOptions::default());
+    }
+
+    #[test]
+    fn try_build_returns_every_issue() {
+        let manifest = Manifest {
+            types: vec!["movie".into()],
+            resources: vec![ManifestResource::Short("meta".into())],
+            ..test_util::manifest()
+        };
+        let err = Builder::new(manifest)
+            .handler(
+                HandlerKind::Stream,
+                |_: &ResourcePath, _: &RequestContext| {
+                    Box::pin(future::ready(Ok(ResourceResponse::Streams {
+                        streams: vec![],
+                    })))
+                },
+            )
+            .handler(
+                HandlerKind::Stream,
+                |_: &ResourcePath, _: &RequestContext| {
+                    Box::pin(future::ready(Ok(ResourceResponse::Streams {
+                        streams: vec![],
+                    })))
+                },
+            )
+            .try_build(ServerOptions::default())
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.issues(),
+            [
+                ValidationIssue::DuplicateHandler {
+                    binding: "stream".into()
+                },
+                ValidationIssue::UnusedHandler {
+                    binding: "stream".into()
+                },
+                ValidationIssue::MissingHandler {
+                    resource: "meta".into()
+                },
+            ]
+        );
+        assert_eq!(err.errors().count(), 3);
+    }
+
+    #[test]
+    fn external_resources_need_no_handler() {
+        let manifest = Manifest {
+            types: vec!["movie".into()],
+            resources: vec![ManifestResource::Short("stream".into())],
+            ..test_util::manifest()
+        };
+        let builder = Builder::new(manifest.clone()).external(HandlerKind::Stream);
+        assert!(builder.validate().is_empty());
+        assert_eq!(
+            Builder::new(manifest).validate(),
+            [
+                ValidationIssue::NoHandlers,
+                ValidationIssue::MissingHandler {
+                    resource: "stream".into()
+                },
+            ]
+        );
+    }
 }
@@ -0,0 +1,376 @@
+use std::net::{IpAddr, SocketAddr};
+
+use bytes::Bytes;
+use http::{Request, Response};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+
+use crate::context::ClientIp;
+use crate::router::{self, ResponseKind, Router};
+
+/// Characters a web server decodes in `PATH_INFO` which are not allowed as they are in a path.
+const PATH: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Answers the request of a CGI script run by the web server: the request is read from the
+/// environment and the response written to stdout.
+///
+/// ```no_run
+/// # use stremio_addon_sdk::router::Router;
+/// # use stremio_addon_sdk::server::serve_cgi;
+/// # fn router() -> Router { unimplemented!() }
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() {
+///     serve_cgi(router()).await.unwrap();
+/// }
+/// ```
+#[cfg(feature = "cgi")]
+pub async fn serve_cgi(router: Router) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use std::io::Write;
+
+    let params = std::env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)));
+    let response = respond(&router, params).await;
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(&encode_response(response))?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Answers the request described by CGI meta-variables, with a 400 when they do not describe a
+/// valid one.
+pub(crate) async fn respond<I>(router: &Router, params: I) -> Response<Bytes>
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    match request_from_params(params) {
+        Ok(request) => router.handle(request).await,
+        Err(err) => router::or_internal_error(
+            router.response_from(ResponseKind::BadRequest(err.to_string())),
+            "CGI request",
+        ),
+    }
+}
+
+/// Builds the request of the CGI meta-variables (RFC 3875) a web server passes to a script or a
+/// FastCGI application. The path is taken from the raw `REQUEST_URI`, without the `SCRIPT_NAME`
+/// in front of it for scripts, so that encoded `&` or `/` in extras and ids are kept as they are.
+/// When a rewrite serves paths which are not under `SCRIPT_NAME`, it is the end of `REQUEST_URI`
+/// that `PATH_INFO` is decoded from. Only when `REQUEST_URI` is not passed, or does not end with
+/// `PATH_INFO`, is it taken from `PATH_INFO`, which is already decoded.
+/// `HTTP_*` variables are turned back into headers and `REMOTE_ADDR` into the client address,
+/// so that handlers see the client as with `serve_http`. The body is left out since
+/// only `GET` requests are answered.
+pub(crate) fn request_from_params<I>(params: I) -> Result<Request<()>, http::Error>
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    let mut builder = Request::builder();
+    let (mut path_info, mut request_uri, mut script_name, mut query) = (None, None, None, None);
+    let (mut remote_ip, mut remote_port) = (None, None);
+    for (name, value) in params {
+        match name.as_str() {
+            "REQUEST_METHOD" => builder = builder.method(value.as_str()),
+            "PATH_INFO" if !value.is_empty() => path_info = Some(value),
+            "REQUEST_URI" => request_uri = Some(value),
+            "SCRIPT_NAME" => script_name = Some(value),
+            "QUERY_STRING" if !value.is_empty() => query = Some(value),
+            "REMOTE_ADDR" => remote_ip = value.parse::<IpAddr>().ok(),
+            "REMOTE_PORT" => remote_port = value.parse::<u16>().ok(),
+            "CONTENT_TYPE" | "CONTENT_LENGTH" => {
+                builder = builder.header(header_name(&name), value)
+            }
+            _ => {
+                if let Some(header) = name.strip_prefix("HTTP_") {
+                    builder = builder.header(header_name(header), value);
+                }
+            }
+        }
+    }
+    let path = match (request_uri, path_info) {
+        (Some(request_uri), path_info) => {
+            let path = request_uri.split('?').next().unwrap_or_default();
+            match script_name
+                .as_deref()
+                .and_then(|script_name| path.strip_prefix(script_name))
+            {
+                Some(rest) if rest.starts_with('/') => rest.to_owned(),
+                // e.g. `/addon/manifest.json` rewritten to `/addon/addon.cgi/manifest.json`
+                _ => match path_info {
+                    Some(path_info) => match encoded_suffix(path, &path_info) {
+                        Some(suffix) => suffix.to_owned(),
+                        None => utf8_percent_encode(&path_info, PATH).to_string(),
+                    },
+                    // nginx passes the whole path as the script name of applications served at
+                    // the root, with no path info
+                    None => path.to_owned(),
+                },
+            }
+        }
+        // the web server decodes the path info, so it is encoded again
+        (None, Some(path_info)) => utf8_percent_encode(&path_info, PATH).to_string(),
+        (None, None) => String::new(),
+    };
+    let path = if path.is_empty() { "/".into() } else { path };
+    let uri = match query {
+        Some(query) => format!("{}?{}", path, query),
+        None => path,
+    };
+    let mut request = builder.uri(uri).body(())?;
+    match (remote_ip, remote_port) {
+        (Some(ip), Some(port)) => {
+            request.extensions_mut().insert(SocketAddr::new(ip, port));
+        }
+        (Some(ip), None) => {
+            request.extensions_mut().insert(ClientIp(ip));
+        }
+        _ => (),
+    }
+    Ok(request)
+}
+
+/// The end of the raw `path`, from one of its `/`, which decodes to `path_info`.
+fn encoded_suffix<'a>(path: &'a str, path_info: &str) -> Option<&'a str> {
+    path.match_indices('/')
+        .map(|(index, _)| &path[index..])
+        .find(|suffix| percent_decode_str(suffix).decode_utf8_lossy() == path_info)
+}
+
+/// Encodes a response as a CGI script writes it: a `Status` line, the headers and the body.
+pub(crate) fn encode_response(response: Response<Bytes>) -> Vec<u8> {
+    let (parts, body) = response.into_parts();
+    let mut output = format!(
+        "Status: {} {}\r\n",
+        parts.status.as_u16(),
+        parts.status.canonical_reason().unwrap_or_default()
+    )
+    .into_bytes();
+    for (name, value) in &parts.headers {
+        output.extend_from_slice(name.as_str().as_bytes());
+        output.extend_from_slice(b": ");
+        output.extend_from_slice(value.as_bytes());
+        output.extend_from_slice(b"\r\n");
+    }
+    output.extend_from_slice(b"\r\n");
+    output.extend_from_slice(&body);
+    output
+}
+
+fn header_name(variable: &str) -> String {
+    variable.replace('_', "-").to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use http::StatusCode;
+    use stremio_core::types::addon::{ResourcePath, ResourceResponse};
+
+    use crate::builder::{Builder, HandlerKind};
+    use crate::cgi;
+    use crate::context::ClientIp;
+    use crate::error::HandlerError;
+    use crate::server::ServerOptions;
+    use crate::test_util;
+
+    #[test]
+    fn request_from_cgi_script_params() {
+        let request = cgi::request_from_params(test_util::params(&[
+            ("REQUEST_METHOD", "GET"),
+            ("SCRIPT_NAME", "/cgi-bin/addon.cgi"),
+            ("PATH_INFO", r#"/{"debrid":"a b"}/manifest.json"#),
+            (
+                "REQUEST_URI",
+                "/cgi-bin/addon.cgi/%7B%22debrid%22:%22a%20b%22%7D/manifest.json?x=1",
+            ),
+            ("QUERY_STRING", "x=1"),
+            ("HTTP_USER_AGENT", "Stremio/4.4"),
+            ("HTTP_X_REQUEST_ID", "abc"),
+            ("REMOTE_ADDR", "203.0.113.7"),
+            ("REMOTE_PORT", "51000"),
+            ("PATH", "/usr/bin"),
+        ]))
+        .unwrap();
+        assert_eq!(
+            request.uri(),
+            "/%7B%22debrid%22:%22a%20b%22%7D/manifest.json?x=1"
+        );
+        assert_eq!(request.headers()["user-agent"], "Stremio/4.4");
+        assert_eq!(request.headers()["x-request-id"], "abc");
+        assert_eq!(request.headers().len(), 2);
+        assert_eq!(
+            request.extensions().get::<SocketAddr>(),
+            Some(&"203.0.113.7:51000".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn encoded_separators_are_kept() {
+        let request = cgi::request_from_params(test_util::params(&[
+            ("REQUEST_METHOD", "GET"),
+            ("SCRIPT_NAME", "/cgi-bin/addon.cgi"),
+            (
+                "PATH_INFO",
+                "/catalog/movie/top/search=rock & roll/a/b.json",
+            ),
+            (
+                "REQUEST_URI",
+                "/cgi-bin/addon.cgi/catalog/movie/top/search=rock%20%26%20roll%2Fa%2Fb.json",
+            ),
+        ]))
+        .unwrap();
+        assert_eq!(
+            request.uri(),
+            "/catalog/movie/top/search=rock%20%26%20roll%2Fa%2Fb.json"
+        );
+    }
+
+    #[test]
+    fn path_info_is_encoded_without_request_uri() {
+        let request = cgi::request_from_params(test_util::params(&[
+            ("REQUEST_METHOD", "GET"),
+            ("PATH_INFO", r#"/{"debrid":"a b"}/manifest.json"#),
+        ]))
+        .unwrap();
+        assert_eq!(
+            request.uri(),
+            "/%7B%22debrid%22:%22a%20b%22%7D/manifest.json"
+        );
+    }
+
+    #[test]
+    fn request_uri_is_the_path_of_applications_at_the_root() {
+        let request = cgi::request_from_params(test_util::params(&[
+            ("REQUEST_METHOD", "GET"),
+            ("SCRIPT_NAME", "/stream/movie/tt1.json"),
+            ("PATH_INFO", ""),
+            ("REQUEST_URI", "/stream/movie/tt1.json?x=1"),
+            ("QUERY_STRING", "x=1"),
+            ("REMOTE_ADDR", "203.0.113.7"),
+        ]))
+        .unwrap();
+        assert_eq!(request.uri(), "/stream/movie/tt1.json?x=1");
+        // without a port the address is only the client's
+        assert_eq!(request.extensions().get::<SocketAddr>(), None);
+        assert_eq!(
+            request.extensions().get::<ClientIp>().map(|ip| ip.0),
+            Some("203.0.113.7".parse().unwrap())
+        );
+        let request =
+            cgi::request_from_params(test_util::params(&[("REQUEST_METHOD", "GET")])).unwrap();
+        assert_eq!(request.uri(), "/");
+    }
+
+    #[test]
+    fn path_info_is_the_path_of_rewritten_requests() {
+        // `RewriteRule ^(.*)$ addon.cgi/$1` in `/stremio/.htaccess`
+        let request = cgi::request_from_params(test_util::params(&[
+            ("REQUEST_METHOD", "GET"),
+            ("SCRIPT_NAME", "/stremio/addon.cgi"),
+            ("PATH_INFO", r#"/{"a":"b c"}/stream/movie/a/b.json"#),
+            (
+                "REQUEST_URI",
+                "/stremio/%7B%22a%22:%22b%20c%22%7D/stream/movie/a%2Fb.json?x=1",
+            ),
+            ("QUERY_STRING", "x=1"),
+        ]))
+        .unwrap();
+        assert_eq!(
+            request.uri(),
+            "/%7B%22a%22:%22b%20c%22%7D/stream/movie/a%2Fb.json?x=1"
+        );
+        // a path info the request uri does not end with is encoded again
+        let request = cgi::request_from_params(test_util::params(&[
+            ("REQUEST_METHOD", "GET"),
+            ("SCRIPT_NAME", "/stremio/addon.cgi"),
+            ("PATH_INFO", "/manifest.json"),
+            ("REQUEST_URI", "/stremio/"),
+        ]))
+        .unwrap();
+        assert_eq!(request.uri(), "/manifest.json");
+    }
+
+    #[tokio::test]
+    async fn rewritten_manifest_is_served() {
+        let router = test_util::stream_router();
+        let response = cgi::respond(
+            &router,
+            test_util::params(&[
+                ("REQUEST_METHOD", "GET"),
+                ("SCRIPT_NAME", "/stremio/addon.cgi"),
+                ("PATH_INFO", "/manifest.json"),
+                ("REQUEST_URI", "/stremio/manifest.json"),
+            ]),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.into_body(),
+            serde_json::to_vec(&test_util::stream_manifest()).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn responses_are_written_with_a_status_line() {
+        let router = test_util::stream_router();
+        let response = cgi::respond(
+            &router,
+            test_util::params(&[
+                ("REQUEST_METHOD", "GET"),
+                ("PATH_INFO", "/stream/movie/tt1.json"),
+            ]),
+        )
+        .await;
+        let output = String::from_utf8(cgi::encode_response(response)).unwrap();
+        assert!(output.starts_with("Status: 200 OK\r\n"), "{}", output);
+        assert!(
+            output.contains("content-type: application/json\r\n"),
+            "{}",
+            output
+        );
+        assert!(output.ends_with("\r\n\r\n{\"streams\":[]}"), "{}", output);
+    }
+
+    #[tokio::test]
+    async fn encoded_slashes_stay_in_ids() {
+        let router = Builder::new(test_util::stream_manifest())
+            .handler(HandlerKind::Stream, |path: &ResourcePath| {
+                match path.id.as_str() {
+                    "a/b" => Ok(ResourceResponse::Streams { streams: vec![] }),
+                    _ => Err(HandlerError::NotFound),
+                }
+            })
+            .build(ServerOptions::default());
+        let response = cgi::respond(
+            &router,
+            test_util::params(&[
+                ("REQUEST_METHOD", "GET"),
+                ("SCRIPT_NAME", "/cgi-bin/addon.cgi"),
+                ("PATH_INFO", "/stream/movie/a/b.json"),
+                ("REQUEST_URI", "/cgi-bin/addon.cgi/stream/movie/a%2Fb.json"),
+            ]),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn invalid_params_are_a_bad_request() {
+        let router = test_util::stream_router();
+        let response =
+            cgi::respond(&router, test_util::params(&[("REQUEST_METHOD", "G E T")])).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response =
+            cgi::respond(&router, test_util::params(&[("REQUEST_METHOD", "POST")])).await;
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+}
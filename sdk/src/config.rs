@@ -0,0 +1,61 @@
+use percent_encoding::percent_decode_str;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// User settings encoded in the leading path segment, e.g. `/{config}/manifest.json`.
+///
+/// Stremio addons conventionally encode the configuration as URI-encoded JSON, so the segment
+/// is percent-decoded and then parsed as JSON. Addons encoding it otherwise, e.g. as base64 or
+/// `key=value|key=value`, read it from `raw` instead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserConfig {
+    raw: String,
+    value: Option<Value>,
+}
+
+impl UserConfig {
+    pub fn new(value: Value) -> Self {
+        Self {
+            raw: value.to_string(),
+            value: Some(value),
+        }
+    }
+
+    /// Config of a decoded segment, parsed as JSON when it is JSON.
+    pub fn from_raw(raw: impl Into<String>) -> Self {
+        let raw = raw.into();
+        Self {
+            value: serde_json::from_str(&raw).ok(),
+            raw,
+        }
+    }
+
+    /// Decodes a segment, which only fails when it is not UTF-8 once decoded.
+    pub(crate) fn from_segment(segment: &str) -> Option<Self> {
+        let raw = percent_decode_str(segment).decode_utf8().ok()?;
+        Some(Self::from_raw(raw))
+    }
+
+    /// The decoded segment as it was sent by the client.
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+
+    /// The config parsed as JSON, if it is.
+    pub fn value(&self) -> Option<&Value> {
+        self.value.as_ref()
+    }
+
+    /// Returns the value of a top-level key when the config is a JSON object.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.value()?.get(key)
+    }
+
+    /// Deserializes the config into the addon's own settings type, failing when it is not JSON.
+    pub fn deserialize<T: DeserializeOwned>(&self) -> serde_json::Result<T> {
+        match &self.value {
+            Some(value) => T::deserialize(value),
+            None => serde_json::from_str(&self.raw),
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use http::header::{ACCEPT_LANGUAGE, USER_AGENT};
+use http::request::Parts;
+use http::HeaderMap;
+
+use crate::config::UserConfig;
+use crate::server::ServerOptions;
+
+const FORWARDED_FOR: &str = "x-forwarded-for";
+const REQUEST_ID: &str = "x-request-id";
+const VERCEL_ID: &str = "x-vercel-id";
+
+/// Address of the client as the platform an adapter serves tells it, e.g. the source IP of API
+/// Gateway, which is trusted unlike the headers of the request.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ClientIp(pub(crate) IpAddr);
+
+/// What a handler knows about the request besides its resource path.
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    headers: HeaderMap,
+    remote_addr: Option<SocketAddr>,
+    client_ip: Option<IpAddr>,
+    config: Option<UserConfig>,
+    request_id: String,
+}
+
+impl RequestContext {
+    pub(crate) fn from_parts(
+        request: &Parts,
+        config: Option<UserConfig>,
+        options: &ServerOptions,
+    ) -> Self {
+        let headers = request.headers.clone();
+        let remote_addr = remote_addr(request);
+        let client_ip = options
+            .trust_forwarded_for
+            .then(|| forwarded_for(&headers))
+            .flatten()
+            .or_else(|| Some(request.extensions.get::<ClientIp>()?.0))
+            .or_else(|| remote_addr.map(|addr| addr.ip()));
+        let request_id = [REQUEST_ID, VERCEL_ID]
+            .into_iter()
+            .find_map(|name| headers.get(name)?.to_str().ok())
+            .map(Into::into)
+            .unwrap_or_else(generate_request_id);
+        Self {
+            headers,
+            remote_addr,
+            client_ip,
+            config,
+            request_id,
+        }
+    }
+
+    /// Sets the user config, e.g. to call an addon's handlers in tests.
+    pub fn with_config(mut self, config: UserConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Headers of the request, e.g. for a middleware to strip or add some before the handler.
+    pub fn headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.headers
+    }
+
+    /// Value of a header, if it is present and valid UTF-8.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)?.to_str().ok()
+    }
+
+    pub fn user_agent(&self) -> Option<&str> {
+        self.header(USER_AGENT.as_str())
+    }
+
+    pub fn accept_language(&self) -> Option<&str> {
+        self.header(ACCEPT_LANGUAGE.as_str())
+    }
+
+    /// Address of the connection the request came in on, when the server stores it as a
+    /// `SocketAddr` request extension like `serve_http` does, or as axum's `ConnectInfo`.
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        self.remote_addr
+    }
+
+    /// Address of the client: the first `X-Forwarded-For` entry when
+    /// `ServerOptions::trust_forwarded_for` is set, the address the platform or the connection
+    /// gives otherwise.
+    pub fn client_ip(&self) -> Option<IpAddr> {
+        self.client_ip
+    }
+
+    /// User config when the addon is installed from `/{config}/manifest.json`.
+    pub fn config(&self) -> Option<&UserConfig> {
+        self.config.as_ref()
+    }
+
+    /// Taken from `X-Request-Id` or `X-Vercel-Id` when present, generated otherwise.
+    pub fn request_id(&self) -> &str {
+        &self.request_id
+    }
+}
+
+/// Context of a request without headers, client address or config.
+impl Default for RequestContext {
+    fn default() -> Self {
+        Self {
+            headers: HeaderMap::new(),
+            remote_addr: None,
+            client_ip: None,
+            config: None,
+            request_id: generate_request_id(),
+        }
+    }
+}
+
+/// Address of the client as the first proxy saw it, i.e. the first `X-Forwarded-For` entry.
+/// Clients can send any value, so it is only trusted behind a proxy which sets it.
+pub(crate) fn forwarded_for(headers: &HeaderMap) -> Option<IpAddr> {
+    headers
+        .get(FORWARDED_FOR)?
+        .to_str()
+        .ok()?
+        .split(',')
+        .next()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+fn remote_addr(request: &Parts) -> Option<SocketAddr> {
+    let addr = request.extensions.get::<SocketAddr>().copied();
+    #[cfg(feature = "axum")]
+    let addr = addr.or_else(|| {
+        let ::axum::extract::ConnectInfo(addr) = request
+            .extensions
+            .get::<::axum::extract::ConnectInfo<SocketAddr>>()?;
+        Some(*addr)
+    });
+    addr
+}
+
+fn generate_request_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_nanos() as u64);
+    format!(
+        "{:016x}-{:08x}",
+        nanos,
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use http::Request;
+    #[cfg(feature = "serverless-vercel")]
+    use vercel_runtime::Body;
+
+    use crate::context::RequestContext;
+    #[cfg(feature = "serverless-vercel")]
+    use crate::request::{self, ServerlessRequest};
+    use crate::server::ServerOptions;
+
+    #[test]
+    fn context_from_hyper_request() {
+        let addr = "192.168.1.10:51000".parse::<SocketAddr>().unwrap();
+        let mut req = Request::builder()
+            .uri("http://127.0.0.1:7070/stream/movie/tt1.json")
+            .header("user-agent", "Stremio/4.4")
+            .header("accept-language", "de-DE")
+            .header("x-forwarded-for", "10.0.0.1")
+            .header("x-request-id", "abc")
+            .body(())
+            .unwrap();
+        req.extensions_mut().insert(addr);
+        let ctx = RequestContext::from_parts(&req.into_parts().0, None, &Default::default());
+        assert_eq!(ctx.user_agent(), Some("Stremio/4.4"));
+        assert_eq!(ctx.accept_language(), Some("de-DE"));
+        assert_eq!(ctx.remote_addr(), Some(addr));
+        // forwarded headers are not trusted when serving directly
+        assert_eq!(ctx.client_ip(), Some(addr.ip()));
+        assert_eq!(ctx.request_id(), "abc");
+        assert!(ctx.config().is_none());
+    }
+
+    #[cfg(feature = "serverless-vercel")]
+    #[test]
+    fn context_from_serverless_request() {
+        let mut req = ServerlessRequest::new(Body::Empty);
+        req.headers_mut()
+            .insert("user-agent", "Stremio/4.4".parse().unwrap());
+        req.headers_mut()
+            .insert("x-forwarded-for", "203.0.113.7, 10.0.0.1".parse().unwrap());
+        req.headers_mut()
+            .insert("x-vercel-id", "fra1::abc".parse().unwrap());
+        let ctx = RequestContext::from_parts(
+            &request::from_serverless(&req).unwrap().into_parts().0,
+            None,
+            &Default::default(),
+        );
+        assert_eq!(ctx.user_agent(), Some("Stremio/4.4"));
+        assert_eq!(ctx.remote_addr(), None);
+        assert_eq!(ctx.client_ip(), Some("203.0.113.7".parse().unwrap()));
+        assert_eq!(ctx.request_id(), "fra1::abc");
+    }
+
+    #[test]
+    fn request_ids_are_generated_and_unique() {
+        let req = || Request::builder().body(()).unwrap().into_parts().0;
+        let first = RequestContext::from_parts(&req(), None, &Default::default());
+        let second = RequestContext::from_parts(&req(), None, &Default::default());
+        assert!(!first.request_id().is_empty());
+        assert_ne!(first.request_id(), second.request_id());
+    }
+
+    #[test]
+    fn forwarded_for_is_only_trusted_when_enabled() {
+        let mut req = Request::builder()
+            .header("x-forwarded-for", "203.0.113.7, 10.0.0.1")
+            .body(())
+            .unwrap();
+        req.extensions_mut()
+            .insert("10.0.0.1:51000".parse::<SocketAddr>().unwrap());
+        let parts = req.into_parts().0;
+        let ctx = RequestContext::from_parts(&parts, None, &Default::default());
+        assert_eq!(ctx.client_ip(), Some("10.0.0.1".parse().unwrap()));
+        let options = ServerOptions::default().with_trust_forwarded_for(true);
+        let ctx = RequestContext::from_parts(&parts, None, &options);
+        assert_eq!(ctx.client_ip(), Some("203.0.113.7".parse().unwrap()));
+        let spoofed = Request::builder()
+            .header("x-forwarded-for", "203.0.113.7")
+            .body(())
+            .unwrap();
+        let ctx = RequestContext::from_parts(&spoofed.into_parts().0, None, &Default::default());
+        assert_eq!(ctx.client_ip(), None);
+    }
+
+    #[cfg(feature = "axum")]
+    #[test]
+    fn remote_addr_from_axum_connect_info() {
+        let addr = "192.168.1.10:51000".parse::<SocketAddr>().unwrap();
+        let mut req = Request::builder().body(()).unwrap();
+        req.extensions_mut()
+            .insert(::axum::extract::ConnectInfo(addr));
+        let ctx = RequestContext::from_parts(&req.into_parts().0, None, &Default::default());
+        assert_eq!(ctx.remote_addr(), Some(addr));
+        assert_eq!(ctx.client_ip(), Some(addr.ip()));
+    }
+}
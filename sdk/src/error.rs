@@ -0,0 +1,222 @@
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+use http::StatusCode;
+use stremio_core::types::addon::ResourceResponse;
+
+pub type HandlerResult = Result<ResourceResponse, HandlerError>;
+
+/// Error returned by a resource handler. The router answers it with the matching status code
+/// and a JSON error body, without the cache headers of successful responses.
+#[derive(Debug)]
+pub enum HandlerError {
+    /// The requested item does not exist.
+    NotFound,
+    /// The request cannot be served as sent, e.g. a malformed id or extra value.
+    BadRequest(String),
+    /// A service the addon depends on failed or is unreachable.
+    Upstream(Box<dyn std::error::Error + Send + Sync + 'static>),
+    /// The addon, or a service it depends on, is rate limited.
+    RateLimited { retry_after: Option<Duration> },
+    /// The addon failed on its own, e.g. the handler panicked.
+    Internal,
+}
+
+impl HandlerError {
+    pub fn upstream<E>(err: E) -> Self
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync + 'static>>,
+    {
+        HandlerError::Upstream(err.into())
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            HandlerError::NotFound => StatusCode::NOT_FOUND,
+            HandlerError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            HandlerError::Upstream(_) => StatusCode::BAD_GATEWAY,
+            HandlerError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            HandlerError::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Message sent to the client; upstream details stay in the server log.
+    pub(crate) fn public_message(&self) -> String {
+        match self {
+            HandlerError::Upstream(_) => "upstream service failed".into(),
+            err => err.to_string(),
+        }
+    }
+}
+
+impl std::error::Error for HandlerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HandlerError::Upstream(err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl Display for HandlerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HandlerError::NotFound => write!(f, "not found"),
+            HandlerError::BadRequest(reason) => write!(f, "bad request: {}", reason),
+            HandlerError::Upstream(err) => write!(f, "upstream service failed: {}", err),
+            HandlerError::RateLimited { .. } => write!(f, "rate limited"),
+            HandlerError::Internal => write!(f, "internal server error"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The addon would misbehave; building fails.
+    Error,
+    /// The addon works, but part of its definition has no effect.
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// Problem found by `Builder::validate` in the combination of manifest and handlers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationIssue {
+    NoHandlers,
+    /// The manifest declares a resource or catalog that no handler serves.
+    MissingHandler {
+        resource: String,
+    },
+    /// A handler is registered for a resource the manifest does not declare.
+    UnusedHandler {
+        binding: String,
+    },
+    /// More than one handler is registered for the same binding; only the first one is kept.
+    DuplicateHandler {
+        binding: String,
+    },
+    /// A catalog handler is registered, but the manifest declares no catalogs of its kind.
+    EmptyCatalogs {
+        resource: String,
+    },
+    /// A handler is bound to a type that is not declared for its resource, so it is never called.
+    UndeclaredType {
+        binding: String,
+        r#type: String,
+    },
+    /// A handler is bound to an id prefix that is not declared for its resource, so it is never
+    /// called.
+    UndeclaredIdPrefix {
+        binding: String,
+        id_prefix: String,
+    },
+    /// The manifest breaks one of the rules Stremio enforces when installing the addon.
+    InvalidManifest {
+        field: String,
+        reason: String,
+    },
+}
+
+impl ValidationIssue {
+    /// Bindings the manifest keeps requests from reaching are warnings, since the other handlers
+    /// of their resource still answer. Handlers of undeclared resources are rejected like the
+    /// JavaScript SDK rejects them.
+    pub fn severity(&self) -> Severity {
+        match self {
+            ValidationIssue::UndeclaredType { .. } | ValidationIssue::UndeclaredIdPrefix { .. } => {
+                Severity::Warning
+            }
+            ValidationIssue::NoHandlers
+            | ValidationIssue::MissingHandler { .. }
+            | ValidationIssue::UnusedHandler { .. }
+            | ValidationIssue::DuplicateHandler { .. }
+            | ValidationIssue::EmptyCatalogs { .. }
+            | ValidationIssue::InvalidManifest { .. } => Severity::Error,
+        }
+    }
+}
+
+impl Display for ValidationIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationIssue::NoHandlers => write!(f, "at least one handler must be defined"),
+            ValidationIssue::MissingHandler { resource } => write!(
+                f,
+                "manifest definition requires handler for '{}', but it is not provided",
+                resource
+            ),
+            ValidationIssue::UnusedHandler { binding } => {
+                write!(f, "manifest.resources does not contain: {}", binding)
+            }
+            ValidationIssue::DuplicateHandler { binding } => {
+                write!(f, "handler for '{}' is already defined", binding)
+            }
+            ValidationIssue::EmptyCatalogs { resource } => write!(
+                f,
+                "manifest.{} is empty, '{}' handler will never be called",
+                if resource == "catalog" {
+                    "catalogs"
+                } else {
+                    "addonCatalogs"
+                },
+                resource
+            ),
+            ValidationIssue::UndeclaredType { binding, r#type } => write!(
+                f,
+                "'{}' handler is bound to type '{}', which is not declared for its resource",
+                binding, r#type
+            ),
+            ValidationIssue::UndeclaredIdPrefix { binding, id_prefix } => write!(
+                f,
+                "'{}' handler is bound to id prefix '{}', which is not declared for its resource",
+                binding, id_prefix
+            ),
+            ValidationIssue::InvalidManifest { field, reason } => {
+                write!(f, "manifest.{} {}", field, reason)
+            }
+        }
+    }
+}
+
+/// Returned by `Builder::try_build` when validation finds at least one error.
+#[derive(Debug)]
+pub struct BuildError {
+    issues: Vec<ValidationIssue>,
+}
+
+impl BuildError {
+    pub(crate) fn new(issues: Vec<ValidationIssue>) -> Self {
+        Self { issues }
+    }
+
+    /// Every issue found, warnings included.
+    pub fn issues(&self) -> &[ValidationIssue] {
+        &self.issues
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity() == Severity::Error)
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "\n--failed to build addon interface-- ")?;
+        for issue in &self.issues {
+            write!(f, "\n{}: {}", issue.severity(), issue)?;
+        }
+        Ok(())
+    }
+}
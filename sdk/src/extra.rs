@@ -0,0 +1,411 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use stremio_core::constants::{SEARCH_EXTRA_NAME, SKIP_EXTRA_NAME};
+use stremio_core::types::addon::{ExtraValue, ManifestCatalog, ResourcePath};
+
+use crate::error::HandlerError;
+
+pub const GENRE_EXTRA_NAME: &str = "genre";
+pub const VIDEO_HASH_EXTRA_NAME: &str = "videoHash";
+pub const VIDEO_SIZE_EXTRA_NAME: &str = "videoSize";
+pub const FILENAME_EXTRA_NAME: &str = "filename";
+
+/// Extra value that could not be parsed into the type the handler expects.
+///
+/// Converts into `HandlerError::BadRequest`, so handlers can use `?` on it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtraError {
+    pub name: String,
+    pub value: String,
+    pub reason: String,
+}
+
+impl std::error::Error for ExtraError {}
+
+impl Display for ExtraError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "extra '{}' has invalid value '{}': {}",
+            self.name, self.value, self.reason
+        )
+    }
+}
+
+impl From<ExtraError> for HandlerError {
+    fn from(err: ExtraError) -> Self {
+        HandlerError::BadRequest(err.to_string())
+    }
+}
+
+/// Typed view of the extra values of a request.
+///
+/// Implement it for an addon's own struct with the helpers of this module:
+///
+/// ```no_run
+/// # use stremio_addon_sdk::extra::{self, ExtraError, FromExtra};
+/// # use stremio_addon_sdk::stremio_core::types::addon::ExtraValue;
+/// struct ChannelExtra {
+///     region: Option<String>,
+///     page: Option<u16>,
+/// }
+///
+/// impl FromExtra for ChannelExtra {
+///     fn from_extra(extra: &[ExtraValue]) -> Result<Self, ExtraError> {
+///         Ok(Self {
+///             region: extra::value(extra, "region").map(Into::into),
+///             page: extra::parse(extra, "page")?,
+///         })
+///     }
+/// }
+/// ```
+pub trait FromExtra: Sized {
+    fn from_extra(extra: &[ExtraValue]) -> Result<Self, ExtraError>;
+
+    fn from_path(path: &ResourcePath) -> Result<Self, ExtraError> {
+        Self::from_extra(&path.extra)
+    }
+}
+
+/// First value of the extra with the given name.
+pub fn value<'a>(extra: &'a [ExtraValue], name: &str) -> Option<&'a str> {
+    extra
+        .iter()
+        .find(|extra| extra.name == name)
+        .map(|extra| extra.value.as_str())
+}
+
+/// Every value of the extra with the given name, in request order.
+pub fn values(extra: &[ExtraValue], name: &str) -> Vec<String> {
+    extra
+        .iter()
+        .filter(|extra| extra.name == name)
+        .map(|extra| extra.value.clone())
+        .collect()
+}
+
+/// Parses the first value of the extra with the given name.
+pub fn parse<T>(extra: &[ExtraValue], name: &str) -> Result<Option<T>, ExtraError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value(extra, name)
+        .map(|value| {
+            value.parse().map_err(|err: T::Err| ExtraError {
+                name: name.into(),
+                value: value.into(),
+                reason: err.to_string(),
+            })
+        })
+        .transpose()
+}
+
+/// Checks the extras of a request against the catalog's declaration and returns the reason of the
+/// first violation.
+pub(crate) fn check_declared(
+    catalog: &ManifestCatalog,
+    extra: &[ExtraValue],
+) -> Result<(), String> {
+    let props = catalog.extra_iter().collect::<Vec<_>>();
+    if let Some(unknown) = extra
+        .iter()
+        .find(|extra| !props.iter().any(|prop| prop.name == extra.name))
+    {
+        return Err(format!(
+            "extra '{}' is not declared for catalog '{}/{}'",
+            unknown.name, catalog.r#type, catalog.id
+        ));
+    }
+    for prop in &props {
+        let values = extra
+            .iter()
+            .filter(|extra| extra.name == prop.name)
+            .collect::<Vec<_>>();
+        if prop.is_required && values.is_empty() {
+            return Err(format!("extra '{}' is required", prop.name));
+        }
+        if values.len() > prop.options_limit.0 {
+            return Err(format!(
+                "extra '{}' accepts at most {} values, but got {}",
+                prop.name,
+                prop.options_limit.0,
+                values.len()
+            ));
+        }
+        if prop.options.is_empty() {
+            continue;
+        }
+        if let Some(invalid) = values
+            .iter()
+            .find(|extra| !prop.options.contains(&extra.value))
+        {
+            return Err(format!(
+                "'{}' is not one of the options of extra '{}'",
+                invalid.value, prop.name
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Extras Stremio sends to catalog handlers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CatalogExtra {
+    pub skip: Option<u32>,
+    pub search: Option<String>,
+    pub genre: Vec<String>,
+}
+
+impl FromExtra for CatalogExtra {
+    fn from_extra(extra: &[ExtraValue]) -> Result<Self, ExtraError> {
+        Ok(Self {
+            skip: parse(extra, SKIP_EXTRA_NAME)?,
+            search: value(extra, SEARCH_EXTRA_NAME).map(Into::into),
+            genre: values(extra, GENRE_EXTRA_NAME),
+        })
+    }
+}
+
+/// Extras Stremio sends to subtitles handlers, describing the video being played.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SubtitlesExtra {
+    pub video_hash: Option<String>,
+    pub video_size: Option<u64>,
+    pub filename: Option<String>,
+}
+
+impl FromExtra for SubtitlesExtra {
+    fn from_extra(extra: &[ExtraValue]) -> Result<Self, ExtraError> {
+        Ok(Self {
+            video_hash: value(extra, VIDEO_HASH_EXTRA_NAME).map(Into::into),
+            video_size: parse(extra, VIDEO_SIZE_EXTRA_NAME)?,
+            filename: value(extra, FILENAME_EXTRA_NAME).map(Into::into),
+        })
+    }
+}
+
+/// Extras of stream requests, describing the video streams are requested for when the client
+/// already has it, e.g. a local file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StreamExtra {
+    pub video_hash: Option<String>,
+    pub video_size: Option<u64>,
+    pub filename: Option<String>,
+}
+
+impl FromExtra for StreamExtra {
+    fn from_extra(extra: &[ExtraValue]) -> Result<Self, ExtraError> {
+        let SubtitlesExtra {
+            video_hash,
+            video_size,
+            filename,
+        } = SubtitlesExtra::from_extra(extra)?;
+        Ok(Self {
+            video_hash,
+            video_size,
+            filename,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use stremio_core::types::addon::{
+        ExtraProp, ExtraValue, ManifestCatalog, ManifestExtra, OptionsLimit, ResourcePath,
+    };
+
+    use crate::error::HandlerError;
+    use crate::extra::{
+        check_declared, CatalogExtra, ExtraError, FromExtra, StreamExtra, SubtitlesExtra,
+    };
+
+    fn extra(name: &str, value: &str) -> ExtraValue {
+        ExtraValue {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+
+    #[test]
+    fn catalog_extra_from_path() {
+        let path = ResourcePath::with_extra(
+            "catalog",
+            "movie",
+            "top",
+            &[
+                extra("genre", "Action"),
+                extra("skip", "100"),
+                extra("search", "the office"),
+                extra("genre", "Drama"),
+            ],
+        );
+        assert_eq!(
+            CatalogExtra::from_path(&path),
+            Ok(CatalogExtra {
+                skip: Some(100),
+                search: Some("the office".into()),
+                genre: vec!["Action".into(), "Drama".into()],
+            })
+        );
+    }
+
+    #[test]
+    fn catalog_extra_defaults_when_missing() {
+        let path = ResourcePath::without_extra("catalog", "movie", "top");
+        assert_eq!(CatalogExtra::from_path(&path), Ok(CatalogExtra::default()));
+    }
+
+    #[test]
+    fn subtitles_extra_from_extra() {
+        let extra = [
+            extra("videoHash", "8e245d9679d31e12"),
+            extra("videoSize", "1351828"),
+            extra("filename", "video.mkv"),
+        ];
+        assert_eq!(
+            SubtitlesExtra::from_extra(&extra),
+            Ok(SubtitlesExtra {
+                video_hash: Some("8e245d9679d31e12".into()),
+                video_size: Some(1351828),
+                filename: Some("video.mkv".into()),
+            })
+        );
+    }
+
+    #[test]
+    fn stream_extra_from_path() {
+        let path = ResourcePath::with_extra(
+            "stream",
+            "movie",
+            "tt0111161",
+            &[
+                extra("filename", "video.mkv"),
+                extra("videoSize", "1351828"),
+            ],
+        );
+        assert_eq!(
+            StreamExtra::from_path(&path),
+            Ok(StreamExtra {
+                video_hash: None,
+                video_size: Some(1351828),
+                filename: Some("video.mkv".into()),
+            })
+        );
+        assert!(StreamExtra::from_extra(&[extra("videoSize", "big")]).is_err());
+    }
+
+    #[test]
+    fn invalid_value_is_bad_request() {
+        let err = CatalogExtra::from_extra(&[extra("skip", "ten")]).unwrap_err();
+        assert_eq!(err.name, "skip");
+        assert_eq!(err.value, "ten");
+        let err = HandlerError::from(err);
+        assert!(matches!(err, HandlerError::BadRequest(_)));
+        assert_eq!(err.status(), http::StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn negative_size_is_rejected() {
+        assert!(matches!(
+            SubtitlesExtra::from_extra(&[extra("videoSize", "-1")]),
+            Err(ExtraError { .. })
+        ));
+    }
+
+    fn genre_catalog() -> ManifestCatalog {
+        ManifestCatalog {
+            r#type: "movie".into(),
+            id: "top".into(),
+            name: None,
+            extra: ManifestExtra::Full {
+                props: vec![
+                    ExtraProp {
+                        name: "genre".into(),
+                        is_required: true,
+                        options: vec!["Action".into(), "Drama".into(), "Comedy".into()],
+                        options_limit: OptionsLimit(2),
+                    },
+                    ExtraProp {
+                        name: "skip".into(),
+                        is_required: false,
+                        options: vec![],
+                        options_limit: OptionsLimit::default(),
+                    },
+                ],
+            },
+        }
+    }
+
+    #[test]
+    fn declared_extras_are_accepted() {
+        let catalog = genre_catalog();
+        assert_eq!(
+            check_declared(&catalog, &[extra("genre", "Action")]),
+            Ok(())
+        );
+        assert_eq!(
+            check_declared(
+                &catalog,
+                &[
+                    extra("genre", "Action"),
+                    extra("genre", "Drama"),
+                    extra("skip", "100")
+                ]
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn undeclared_extras_are_rejected() {
+        let catalog = genre_catalog();
+        let cases = [
+            (vec![], "extra 'genre' is required"),
+            (
+                vec![extra("genre", "Action"), extra("search", "x")],
+                "extra 'search' is not declared for catalog 'movie/top'",
+            ),
+            (
+                vec![extra("genre", "Horror")],
+                "'Horror' is not one of the options of extra 'genre'",
+            ),
+            (
+                vec![
+                    extra("genre", "Action"),
+                    extra("genre", "Drama"),
+                    extra("genre", "Comedy"),
+                ],
+                "extra 'genre' accepts at most 2 values, but got 3",
+            ),
+            (
+                vec![
+                    extra("genre", "Action"),
+                    extra("skip", "1"),
+                    extra("skip", "2"),
+                ],
+                "extra 'skip' accepts at most 1 values, but got 2",
+            ),
+        ];
+        for (extra, reason) in cases {
+            assert_eq!(check_declared(&catalog, &extra), Err(reason.into()));
+        }
+    }
+
+    #[test]
+    fn short_extra_declaration_is_enforced() {
+        let catalog = ManifestCatalog {
+            extra: ManifestExtra::Short {
+                required: vec!["search".into()],
+                supported: vec!["search".into(), "skip".into()],
+            },
+            ..genre_catalog()
+        };
+        assert_eq!(check_declared(&catalog, &[extra("search", "x")]), Ok(()));
+        assert_eq!(
+            check_declared(&catalog, &[extra("skip", "1")]),
+            Err("extra 'search' is required".into())
+        );
+    }
+}
@@ -0,0 +1,552 @@
+use std::error::Error;
+use std::io;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+
+use crate::cgi;
+use crate::router::Router;
+
+const VERSION: u8 = 1;
+
+const BEGIN_REQUEST: u8 = 1;
+const ABORT_REQUEST: u8 = 2;
+const END_REQUEST: u8 = 3;
+const PARAMS: u8 = 4;
+const STDIN: u8 = 5;
+const STDOUT: u8 = 6;
+const GET_VALUES: u8 = 9;
+const GET_VALUES_RESULT: u8 = 10;
+const UNKNOWN_TYPE: u8 = 11;
+
+const RESPONDER: u16 = 1;
+const KEEP_CONN: u8 = 1;
+const REQUEST_COMPLETE: u8 = 0;
+const CANT_MPX_CONN: u8 = 1;
+const UNKNOWN_ROLE: u8 = 3;
+/// Requests are answered one after the other, so web servers are told not to interleave them.
+const MPXS_CONNS: &str = "FCGI_MPXS_CONNS";
+/// Largest params of a request, well above what web servers send for a `GET`.
+const MAX_PARAMS_LENGTH: usize = 64 * 1024;
+
+/// Listener `serve_fastcgi` accepts the connections of the web server on.
+#[derive(Debug)]
+pub enum FastCgiListener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+impl From<TcpListener> for FastCgiListener {
+    fn from(listener: TcpListener) -> Self {
+        Self::Tcp(listener)
+    }
+}
+
+#[cfg(unix)]
+impl From<UnixListener> for FastCgiListener {
+    fn from(listener: UnixListener) -> Self {
+        Self::Unix(listener)
+    }
+}
+
+/// Serves `Router` as a FastCGI application on a TCP or Unix socket listener, e.g. behind nginx
+/// with `fastcgi_pass`:
+///
+/// ```no_run
+/// # use stremio_addon_sdk::router::Router;
+/// # use stremio_addon_sdk::server::serve_fastcgi;
+/// # use tokio::net::UnixListener;
+/// # async fn serve(router: Router) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+/// serve_fastcgi(router, UnixListener::bind("/run/addon.sock")?).await
+/// # }
+/// ```
+pub async fn serve_fastcgi(
+    router: Router,
+    listener: impl Into<FastCgiListener>,
+) -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
+    match listener.into() {
+        FastCgiListener::Tcp(listener) => {
+            let addr = listener.local_addr()?;
+            log::info!("Running on: {}", addr);
+            loop {
+                let (stream, _) = listener.accept().await?;
+                spawn_connection(router.clone(), stream);
+            }
+        }
+        #[cfg(unix)]
+        FastCgiListener::Unix(listener) => {
+            let addr = listener.local_addr()?;
+            log::info!("Running on: {:?}", addr);
+            loop {
+                let (stream, _) = listener.accept().await?;
+                spawn_connection(router.clone(), stream);
+            }
+        }
+    }
+}
+
+fn spawn_connection<S>(router: Router, stream: S)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    tokio::task::spawn(async move {
+        if let Err(err) = serve_connection(&router, stream).await {
+            log::error!("connection error: {:?}", err);
+        }
+    });
+}
+
+/// The request whose params and stdin are being received.
+struct Pending {
+    request_id: u16,
+    keep_conn: bool,
+    params: Vec<u8>,
+}
+
+/// Answers the requests of a connection until the web server closes it, or until a request
+/// without `FCGI_KEEP_CONN` is answered.
+async fn serve_connection<S>(router: &Router, mut stream: S) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut pending: Option<Pending> = None;
+    while let Some(record) = read_record(&mut stream).await? {
+        let mut output = vec![];
+        let mut close = false;
+        let is_pending = pending
+            .as_ref()
+            .is_some_and(|request| request.request_id == record.request_id);
+        match record.kind {
+            BEGIN_REQUEST => {
+                let [role_1, role_0, flags, ..] = record.content[..] else {
+                    return Err(invalid_data("truncated begin request body"));
+                };
+                if pending.is_some() {
+                    end_request(&mut output, record.request_id, CANT_MPX_CONN);
+                } else if u16::from_be_bytes([role_1, role_0]) != RESPONDER {
+                    end_request(&mut output, record.request_id, UNKNOWN_ROLE);
+                } else {
+                    pending = Some(Pending {
+                        request_id: record.request_id,
+                        keep_conn: flags & KEEP_CONN != 0,
+                        params: vec![],
+                    });
+                }
+            }
+            PARAMS if is_pending => {
+                let params = &mut pending.as_mut().unwrap().params;
+                if params.len() + record.content.len() > MAX_PARAMS_LENGTH {
+                    return Err(invalid_data("params too long"));
+                }
+                params.extend_from_slice(&record.content);
+            }
+            // the stdin is skipped since only `GET` requests are answered, its end is the end
+            // of the request
+            STDIN if is_pending && record.content.is_empty() => {
+                let request = pending.take().unwrap();
+                let params = decode_params(&request.params)?;
+                let response = cgi::respond(router, params).await;
+                encode_stream(
+                    &mut output,
+                    STDOUT,
+                    record.request_id,
+                    &cgi::encode_response(response),
+                );
+                end_request(&mut output, record.request_id, REQUEST_COMPLETE);
+                close = !request.keep_conn;
+            }
+            ABORT_REQUEST if is_pending => {
+                let request = pending.take().unwrap();
+                end_request(&mut output, record.request_id, REQUEST_COMPLETE);
+                close = !request.keep_conn;
+            }
+            GET_VALUES => {
+                let values = decode_params(&record.content)?
+                    .into_iter()
+                    .filter(|(name, _)| name == MPXS_CONNS)
+                    .map(|(name, _)| (name, "0".to_owned()))
+                    .collect::<Vec<_>>();
+                encode_record(&mut output, GET_VALUES_RESULT, 0, &encode_params(&values));
+            }
+            kind if record.request_id == 0 => {
+                encode_record(&mut output, UNKNOWN_TYPE, 0, &[kind, 0, 0, 0, 0, 0, 0, 0]);
+            }
+            _ => (),
+        }
+        if !output.is_empty() {
+            stream.write_all(&output).await?;
+            stream.flush().await?;
+        }
+        if close {
+            return stream.shutdown().await;
+        }
+    }
+    Ok(())
+}
+
+struct Record {
+    kind: u8,
+    request_id: u16,
+    content: Vec<u8>,
+}
+
+/// Reads the next record, or `None` when the connection is closed between records.
+async fn read_record<R>(reader: &mut R) -> io::Result<Option<Record>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut header = [0; 8];
+    match reader.read_exact(&mut header).await {
+        Ok(_) => (),
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+    if header[0] != VERSION {
+        return Err(invalid_data("unsupported FastCGI version"));
+    }
+    let length = u16::from_be_bytes([header[4], header[5]]) as usize;
+    let mut content = vec![0; length + header[6] as usize];
+    reader.read_exact(&mut content).await?;
+    content.truncate(length);
+    Ok(Some(Record {
+        kind: header[1],
+        request_id: u16::from_be_bytes([header[2], header[3]]),
+        content,
+    }))
+}
+
+/// Encodes a record, padded to a multiple of 8 bytes. The content must fit in a record.
+fn encode_record(output: &mut Vec<u8>, kind: u8, request_id: u16, content: &[u8]) {
+    let padding = (8 - content.len() % 8) % 8;
+    output.extend_from_slice(&[VERSION, kind]);
+    output.extend_from_slice(&request_id.to_be_bytes());
+    output.extend_from_slice(&(content.len() as u16).to_be_bytes());
+    output.extend_from_slice(&[padding as u8, 0]);
+    output.extend_from_slice(content);
+    output.resize(output.len() + padding, 0);
+}
+
+/// Encodes a stream such as stdout in as many records as it takes, closed by an empty one.
+fn encode_stream(output: &mut Vec<u8>, kind: u8, request_id: u16, content: &[u8]) {
+    for chunk in content.chunks(u16::MAX as usize) {
+        encode_record(output, kind, request_id, chunk);
+    }
+    encode_record(output, kind, request_id, &[]);
+}
+
+fn end_request(output: &mut Vec<u8>, request_id: u16, protocol_status: u8) {
+    encode_record(
+        output,
+        END_REQUEST,
+        request_id,
+        &[0, 0, 0, 0, protocol_status, 0, 0, 0],
+    );
+}
+
+/// Decodes name-value pairs, whose lengths take 1 byte below 128 and 4 bytes otherwise.
+fn decode_params(content: &[u8]) -> io::Result<Vec<(String, String)>> {
+    let mut params = vec![];
+    let mut rest = content;
+    while !rest.is_empty() {
+        let name_length = decode_length(&mut rest)?;
+        let value_length = decode_length(&mut rest)?;
+        // two 31 bit lengths overflow a 32 bit usize
+        let length = name_length
+            .checked_add(value_length)
+            .ok_or_else(|| invalid_data("name-value pair too long"))?;
+        if rest.len() < length {
+            return Err(invalid_data("truncated name-value pair"));
+        }
+        let (name, value) = rest[..length].split_at(name_length);
+        params.push((
+            String::from_utf8_lossy(name).into_owned(),
+            String::from_utf8_lossy(value).into_owned(),
+        ));
+        rest = &rest[length..];
+    }
+    Ok(params)
+}
+
+fn decode_length(rest: &mut &[u8]) -> io::Result<usize> {
+    match **rest {
+        [length, ..] if length < 0x80 => {
+            *rest = &rest[1..];
+            Ok(length as usize)
+        }
+        [b3, b2, b1, b0, ..] => {
+            *rest = &rest[4..];
+            Ok(u32::from_be_bytes([b3 & 0x7f, b2, b1, b0]) as usize)
+        }
+        _ => Err(invalid_data("truncated name-value pair")),
+    }
+}
+
+fn encode_params(params: &[(String, String)]) -> Vec<u8> {
+    let mut content = vec![];
+    for (name, value) in params {
+        for length in [name.len(), value.len()] {
+            if length < 0x80 {
+                content.push(length as u8);
+            } else {
+                content.extend_from_slice(&(length as u32 | 0x8000_0000).to_be_bytes());
+            }
+        }
+        content.extend_from_slice(name.as_bytes());
+        content.extend_from_slice(value.as_bytes());
+    }
+    content
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    #[cfg(unix)]
+    use tokio::net::{UnixListener, UnixStream};
+
+    use crate::fastcgi::{self, Record};
+    use crate::test_util;
+
+    /// Sends a `GET` request the way a web server does, and returns the stdout and the protocol
+    /// status of its answer.
+    async fn get<S>(stream: &mut S, request_id: u16, path: &str, keep_conn: bool) -> (String, u8)
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut input = vec![];
+        let flags = if keep_conn { fastcgi::KEEP_CONN } else { 0 };
+        fastcgi::encode_record(
+            &mut input,
+            fastcgi::BEGIN_REQUEST,
+            request_id,
+            &[0, 1, flags, 0, 0, 0, 0, 0],
+        );
+        let params = fastcgi::encode_params(&test_util::params(&[
+            ("REQUEST_METHOD", "GET"),
+            ("REQUEST_URI", path),
+            ("HTTP_USER_AGENT", "nginx"),
+        ]));
+        // params may be split anywhere across records
+        let (first, second) = params.split_at(5);
+        fastcgi::encode_record(&mut input, fastcgi::PARAMS, request_id, first);
+        fastcgi::encode_stream(&mut input, fastcgi::PARAMS, request_id, second);
+        fastcgi::encode_stream(&mut input, fastcgi::STDIN, request_id, &[]);
+        stream.write_all(&input).await.unwrap();
+        let mut stdout = vec![];
+        loop {
+            let Record {
+                kind,
+                request_id: id,
+                content,
+            } = fastcgi::read_record(stream).await.unwrap().unwrap();
+            assert_eq!(id, request_id);
+            match kind {
+                fastcgi::STDOUT => stdout.extend(content),
+                fastcgi::END_REQUEST => {
+                    return (String::from_utf8(stdout).unwrap(), content[4]);
+                }
+                kind => panic!("unexpected record type {}", kind),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_are_answered_on_kept_connections() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let router = test_util::stream_router();
+        let connection =
+            tokio::spawn(async move { fastcgi::serve_connection(&router, server).await });
+        let (stdout, status) = get(&mut client, 1, "/stream/movie/tt1.json", true).await;
+        assert_eq!(status, fastcgi::REQUEST_COMPLETE);
+        assert!(stdout.starts_with("Status: 200 OK\r\n"), "{}", stdout);
+        assert!(stdout.ends_with("\r\n\r\n{\"streams\":[]}"), "{}", stdout);
+        let (stdout, _) = get(&mut client, 2, "/stream/movie", true).await;
+        assert!(
+            stdout.starts_with("Status: 400 Bad Request\r\n"),
+            "{}",
+            stdout
+        );
+        let (stdout, _) = get(&mut client, 3, "/manifest.json", false).await;
+        assert!(stdout.contains("\"resources\":[\"stream\"]"), "{}", stdout);
+        // the connection is closed after a request without keep conn
+        assert!(fastcgi::read_record(&mut client).await.unwrap().is_none());
+        connection.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn management_records_and_other_roles() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let router = test_util::stream_router();
+        tokio::spawn(async move { fastcgi::serve_connection(&router, server).await });
+        let mut input = vec![];
+        let names = test_util::params(&[("FCGI_MPXS_CONNS", ""), ("FCGI_MAX_REQS", "")]);
+        fastcgi::encode_record(
+            &mut input,
+            fastcgi::GET_VALUES,
+            0,
+            &fastcgi::encode_params(&names),
+        );
+        fastcgi::encode_record(&mut input, 42, 0, &[]);
+        // authorizer
+        fastcgi::encode_record(
+            &mut input,
+            fastcgi::BEGIN_REQUEST,
+            1,
+            &[0, 2, 0, 0, 0, 0, 0, 0],
+        );
+        client.write_all(&input).await.unwrap();
+        let values = fastcgi::read_record(&mut client).await.unwrap().unwrap();
+        assert_eq!(values.kind, fastcgi::GET_VALUES_RESULT);
+        assert_eq!(
+            fastcgi::decode_params(&values.content).unwrap(),
+            test_util::params(&[("FCGI_MPXS_CONNS", "0")])
+        );
+        let unknown = fastcgi::read_record(&mut client).await.unwrap().unwrap();
+        assert_eq!(unknown.kind, fastcgi::UNKNOWN_TYPE);
+        assert_eq!(unknown.content[0], 42);
+        let end = fastcgi::read_record(&mut client).await.unwrap().unwrap();
+        assert_eq!(end.kind, fastcgi::END_REQUEST);
+        assert_eq!(end.content[4], fastcgi::UNKNOWN_ROLE);
+    }
+
+    #[test]
+    fn params_with_long_values_round_trip() {
+        let params = test_util::params(&[("HTTP_COOKIE", &"a".repeat(300)), ("PATH_INFO", "")]);
+        let content = fastcgi::encode_params(&params);
+        assert_eq!(&content[..5], &[11, 0x80, 0, 1, 44]);
+        assert_eq!(fastcgi::decode_params(&content).unwrap(), params);
+        assert!(fastcgi::decode_params(&content[..20]).is_err());
+    }
+
+    #[test]
+    fn params_with_the_largest_lengths_are_invalid_data() {
+        let content = [[0xff; 4], [0xff; 4]].concat();
+        let err = fastcgi::decode_params(&content).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn answers_records_written_as_in_the_spec() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let router = test_util::stream_router();
+        tokio::spawn(async move { fastcgi::serve_connection(&router, server).await });
+        let user_agent = "a".repeat(130);
+        let mut input = vec![];
+        // FCGI_BEGIN_REQUEST for request 1: FCGI_RESPONDER, without FCGI_KEEP_CONN
+        input.extend_from_slice(&[1, 1, 0, 1, 0, 8, 0, 0]);
+        input.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+        // FCGI_PARAMS of 204 bytes padded with 4, the last value has a 4 byte length
+        input.extend_from_slice(&[1, 4, 0, 1, 0, 204, 4, 0]);
+        input.extend_from_slice(&[14, 3]);
+        input.extend_from_slice(b"REQUEST_METHODGET");
+        input.extend_from_slice(&[11, 22]);
+        input.extend_from_slice(b"REQUEST_URI/stream/movie/tt1.json");
+        input.extend_from_slice(&[15, 0x80, 0, 0, 130]);
+        input.extend_from_slice(b"HTTP_USER_AGENT");
+        input.extend_from_slice(user_agent.as_bytes());
+        input.extend_from_slice(&[0; 4]);
+        // end of FCGI_PARAMS and FCGI_STDIN
+        input.extend_from_slice(&[1, 4, 0, 1, 0, 0, 0, 0]);
+        input.extend_from_slice(&[1, 5, 0, 1, 0, 0, 0, 0]);
+        client.write_all(&input).await.unwrap();
+        let mut output = vec![];
+        client.read_to_end(&mut output).await.unwrap();
+
+        // FCGI_STDOUT with the response, then its end and FCGI_END_REQUEST
+        assert_eq!(output[..4], [1, 6, 0, 1]);
+        let length = u16::from_be_bytes([output[4], output[5]]) as usize;
+        let padding = output[6] as usize;
+        assert_eq!((length + padding) % 8, 0);
+        let stdout = String::from_utf8(output[8..8 + length].to_vec()).unwrap();
+        assert!(stdout.starts_with("Status: 200 OK\r\n"), "{}", stdout);
+        assert!(stdout.ends_with("\r\n\r\n{\"streams\":[]}"), "{}", stdout);
+        assert_eq!(
+            output[8 + length + padding..],
+            [
+                1, 6, 0, 1, 0, 0, 0, 0, //
+                1, 3, 0, 1, 0, 8, 0, 0, //
+                0, 0, 0, 0, 0, 0, 0, 0,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn requests_are_not_multiplexed() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let router = test_util::stream_router();
+        tokio::spawn(async move { fastcgi::serve_connection(&router, server).await });
+        let mut input = vec![];
+        fastcgi::encode_record(
+            &mut input,
+            fastcgi::BEGIN_REQUEST,
+            1,
+            &[0, 1, fastcgi::KEEP_CONN, 0, 0, 0, 0, 0],
+        );
+        fastcgi::encode_record(
+            &mut input,
+            fastcgi::BEGIN_REQUEST,
+            2,
+            &[0, 1, fastcgi::KEEP_CONN, 0, 0, 0, 0, 0],
+        );
+        client.write_all(&input).await.unwrap();
+        let end = fastcgi::read_record(&mut client).await.unwrap().unwrap();
+        assert_eq!((end.kind, end.request_id), (fastcgi::END_REQUEST, 2));
+        assert_eq!(end.content[4], fastcgi::CANT_MPX_CONN);
+    }
+
+    #[tokio::test]
+    async fn params_are_capped() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let router = test_util::stream_router();
+        let connection =
+            tokio::spawn(async move { fastcgi::serve_connection(&router, server).await });
+        let mut input = vec![];
+        fastcgi::encode_record(
+            &mut input,
+            fastcgi::BEGIN_REQUEST,
+            1,
+            &[0, 1, 0, 0, 0, 0, 0, 0],
+        );
+        let cookie = "a".repeat(fastcgi::MAX_PARAMS_LENGTH);
+        let params = fastcgi::encode_params(&test_util::params(&[("HTTP_COOKIE", &cookie)]));
+        for chunk in params.chunks(u16::MAX as usize) {
+            fastcgi::encode_record(&mut input, fastcgi::PARAMS, 1, chunk);
+        }
+        // the connection is closed before all of it is written
+        let _ = client.write_all(&input).await;
+        assert!(connection.await.unwrap().is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn serves_on_a_unix_socket() {
+        let path = std::env::temp_dir().join(format!("stremio-addon-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        tokio::spawn(fastcgi::serve_fastcgi(test_util::stream_router(), listener));
+        let mut stream = UnixStream::connect(&path).await.unwrap();
+        let (stdout, status) = get(&mut stream, 1, "/stream/movie/tt1.json", false).await;
+        assert_eq!(status, fastcgi::REQUEST_COMPLETE);
+        assert!(stdout.ends_with("{\"streams\":[]}"), "{}", stdout);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn serves_on_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(fastcgi::serve_fastcgi(test_util::stream_router(), listener));
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let (stdout, _) = get(&mut stream, 7, "/stream/movie/tt1.json?x=1", false).await;
+        assert!(stdout.starts_with("Status: 200 OK\r\n"), "{}", stdout);
+    }
+}
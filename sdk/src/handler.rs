@@ -0,0 +1,502 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use futures::future::{self, BoxFuture};
+use futures::FutureExt;
+use stremio_core::types::addon::{ResourcePath, ResourceResponse};
+
+use crate::builder::{handler_fn, HandlerFn};
+use crate::context::RequestContext;
+use crate::error::{HandlerError, HandlerResult};
+use crate::state::State;
+
+/// What a handler returns: the response itself, or a `Result` of it for handlers that can fail.
+///
+/// `T` is a [`ResourceResponse`] for handlers registered with `Builder::handler`, and the
+/// typed response of the resource for the others, e.g. `Vec<Stream>` for `Builder::stream`.
+pub trait IntoHandlerResult<T = ResourceResponse> {
+    fn into_handler_result(self) -> Result<T, HandlerError>;
+}
+
+impl<T> IntoHandlerResult<T> for T {
+    fn into_handler_result(self) -> Result<T, HandlerError> {
+        Ok(self)
+    }
+}
+
+impl<T> IntoHandlerResult<T> for Result<T, HandlerError> {
+    fn into_handler_result(self) -> Result<T, HandlerError> {
+        self
+    }
+}
+
+/// Function or closure that can be registered with `Builder::handler` and the typed methods
+/// such as `Builder::stream`.
+///
+/// It is implemented for:
+///
+/// - `async fn(ResourcePath)`, `async fn(ResourcePath, RequestContext)` and the same with a
+///   [`State<S>`] first, which receive their own copy of the path and context;
+/// - `fn(&ResourcePath)` and `fn(&ResourcePath, &RequestContext)`, with or without a
+///   [`State<S>`] first, which answer right away;
+/// - closures taking `&ResourcePath` and `&RequestContext` that return a `'static` future;
+/// - `fn(&ResourcePath, &RequestContext) -> BoxFuture<HandlerResult>`, whose future may
+///   borrow its arguments.
+///
+/// Each of them returns anything implementing [`IntoHandlerResult<T>`], except the last one.
+/// Closures need their argument types written out, since several forms take two arguments:
+///
+/// ```no_run
+/// # use stremio_addon_sdk::builder::Builder;
+/// # use stremio_addon_sdk::error::HandlerError;
+/// # use stremio_addon_sdk::stremio_core::types::addon::{Manifest, ResourcePath};
+/// # use stremio_addon_sdk::stremio_core::types::resource::{MetaItem, Stream};
+/// # async fn find_streams(id: &str) -> Result<Vec<Stream>, HandlerError> { unimplemented!() }
+/// # async fn find_meta(id: &str) -> Result<Option<MetaItem>, HandlerError> { unimplemented!() }
+/// # fn builder(manifest: Manifest) -> Builder {
+/// async fn streams(path: ResourcePath) -> Result<Vec<Stream>, HandlerError> {
+///     find_streams(&path.id).await
+/// }
+///
+/// Builder::new(manifest)
+///     .stream(streams)
+///     .meta(|path: ResourcePath| async move { find_meta(&path.id).await })
+/// # }
+/// ```
+///
+/// `M` only tells the implementations apart and is inferred.
+pub trait IntoHandler<M, S, T = ResourceResponse> {
+    /// Boxes the handler, with `respond` wrapping what it returns into a response.
+    fn into_handler(self, state: Arc<S>, respond: fn(T) -> HandlerResult) -> Arc<HandlerFn>;
+}
+
+/// Types telling the implementations of [`IntoHandler`] apart.
+pub mod marker {
+    pub struct Boxed;
+    pub struct Borrowed;
+    pub struct Blocking;
+    pub struct BlockingWithContext;
+    pub struct BlockingWithState;
+    pub struct BlockingWithStateAndContext;
+    pub struct Owned;
+    pub struct OwnedWithContext;
+    pub struct OwnedWithState;
+    pub struct OwnedWithStateAndContext;
+}
+
+impl<F, S> IntoHandler<marker::Boxed, S> for F
+where
+    F: for<'a> Fn(&'a ResourcePath, &'a RequestContext) -> BoxFuture<'a, HandlerResult>
+        + Send
+        + Sync
+        + 'static,
+{
+    fn into_handler(
+        self,
+        _: Arc<S>,
+        respond: fn(ResourceResponse) -> HandlerResult,
+    ) -> Arc<HandlerFn> {
+        handler_fn(move |path, ctx| {
+            self(path, ctx)
+                .map(move |result| result.and_then(respond))
+                .boxed()
+        })
+    }
+}
+
+impl<F, Fut, R, S, T> IntoHandler<marker::Borrowed, S, T> for F
+where
+    F: Fn(&ResourcePath, &RequestContext) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = R> + Send + 'static,
+    R: IntoHandlerResult<T> + 'static,
+    T: 'static,
+{
+    fn into_handler(self, _: Arc<S>, respond: fn(T) -> HandlerResult) -> Arc<HandlerFn> {
+        handler_fn(move |path, ctx| {
+            self(path, ctx)
+                .map(move |result| result.into_handler_result().and_then(respond))
+                .boxed()
+        })
+    }
+}
+
+impl<F, R, S, T> IntoHandler<marker::Blocking, S, T> for F
+where
+    F: Fn(&ResourcePath) -> R + Send + Sync + 'static,
+    R: IntoHandlerResult<T>,
+    T: 'static,
+{
+    fn into_handler(self, _: Arc<S>, respond: fn(T) -> HandlerResult) -> Arc<HandlerFn> {
+        handler_fn(move |path, _| {
+            future::ready(self(path).into_handler_result().and_then(respond)).boxed()
+        })
+    }
+}
+
+impl<F, R, S, T> IntoHandler<marker::BlockingWithContext, S, T> for F
+where
+    F: Fn(&ResourcePath, &RequestContext) -> R + Send + Sync + 'static,
+    R: IntoHandlerResult<T>,
+    T: 'static,
+{
+    fn into_handler(self, _: Arc<S>, respond: fn(T) -> HandlerResult) -> Arc<HandlerFn> {
+        handler_fn(move |path, ctx| {
+            future::ready(self(path, ctx).into_handler_result().and_then(respond)).boxed()
+        })
+    }
+}
+
+impl<F, R, S, T> IntoHandler<marker::BlockingWithState, S, T> for F
+where
+    S: Send + Sync + 'static,
+    F: Fn(State<S>, &ResourcePath) -> R + Send + Sync + 'static,
+    R: IntoHandlerResult<T>,
+    T: 'static,
+{
+    fn into_handler(self, state: Arc<S>, respond: fn(T) -> HandlerResult) -> Arc<HandlerFn> {
+        handler_fn(move |path, _| {
+            let result = self(State(state.clone()), path).into_handler_result();
+            future::ready(result.and_then(respond)).boxed()
+        })
+    }
+}
+
+impl<F, R, S, T> IntoHandler<marker::BlockingWithStateAndContext, S, T> for F
+where
+    S: Send + Sync + 'static,
+    F: Fn(State<S>, &ResourcePath, &RequestContext) -> R + Send + Sync + 'static,
+    R: IntoHandlerResult<T>,
+    T: 'static,
+{
+    fn into_handler(self, state: Arc<S>, respond: fn(T) -> HandlerResult) -> Arc<HandlerFn> {
+        handler_fn(move |path, ctx| {
+            let result = self(State(state.clone()), path, ctx).into_handler_result();
+            future::ready(result.and_then(respond)).boxed()
+        })
+    }
+}
+
+impl<F, Fut, R, S, T> IntoHandler<marker::Owned, S, T> for F
+where
+    F: Fn(ResourcePath) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = R> + Send + 'static,
+    R: IntoHandlerResult<T> + 'static,
+    T: 'static,
+{
+    fn into_handler(self, _: Arc<S>, respond: fn(T) -> HandlerResult) -> Arc<HandlerFn> {
+        handler_fn(move |path, _| {
+            self(path.clone())
+                .map(move |result| result.into_handler_result().and_then(respond))
+                .boxed()
+        })
+    }
+}
+
+impl<F, Fut, R, S, T> IntoHandler<marker::OwnedWithContext, S, T> for F
+where
+    F: Fn(ResourcePath, RequestContext) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = R> + Send + 'static,
+    R: IntoHandlerResult<T> + 'static,
+    T: 'static,
+{
+    fn into_handler(self, _: Arc<S>, respond: fn(T) -> HandlerResult) -> Arc<HandlerFn> {
+        handler_fn(move |path, ctx| {
+            self(path.clone(), ctx.clone())
+                .map(move |result| result.into_handler_result().and_then(respond))
+                .boxed()
+        })
+    }
+}
+
+impl<F, Fut, R, S, T> IntoHandler<marker::OwnedWithState, S, T> for F
+where
+    S: Send + Sync + 'static,
+    F: Fn(State<S>, ResourcePath) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = R> + Send + 'static,
+    R: IntoHandlerResult<T> + 'static,
+    T: 'static,
+{
+    fn into_handler(self, state: Arc<S>, respond: fn(T) -> HandlerResult) -> Arc<HandlerFn> {
+        handler_fn(move |path, _| {
+            self(State(state.clone()), path.clone())
+                .map(move |result| result.into_handler_result().and_then(respond))
+                .boxed()
+        })
+    }
+}
+
+impl<F, Fut, R, S, T> IntoHandler<marker::OwnedWithStateAndContext, S, T> for F
+where
+    S: Send + Sync + 'static,
+    F: Fn(State<S>, ResourcePath, RequestContext) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = R> + Send + 'static,
+    R: IntoHandlerResult<T> + 'static,
+    T: 'static,
+{
+    fn into_handler(self, state: Arc<S>, respond: fn(T) -> HandlerResult) -> Arc<HandlerFn> {
+        handler_fn(move |path, ctx| {
+            self(State(state.clone()), path.clone(), ctx.clone())
+                .map(move |result| result.into_handler_result().and_then(respond))
+                .boxed()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::future::BoxFuture;
+    use http::StatusCode;
+    use serde_json::json;
+    use stremio_core::types::addon::{
+        Manifest, ManifestCatalog, ManifestExtra, ManifestResource, ResourcePath, ResourceResponse,
+    };
+    use stremio_core::types::resource::{MetaItem, MetaItemPreview, Stream, Subtitles};
+
+    use crate::builder::{Builder, HandlerKind};
+    use crate::context::RequestContext;
+    use crate::error::{HandlerError, HandlerResult};
+    use crate::extra::{FromExtra, SubtitlesExtra};
+    use crate::manifest::ManifestBuilder;
+    use crate::server::ServerOptions;
+    use crate::state::State;
+    use crate::test_util;
+
+    fn manifest() -> Manifest {
+        Manifest {
+            types: vec!["movie".into(), "series".into()],
+            resources: vec![
+                ManifestResource::Short("meta".into()),
+                ManifestResource::Short("stream".into()),
+                ManifestResource::Short("subtitles".into()),
+            ],
+            ..test_util::manifest()
+        }
+    }
+
+    async fn streams(path: ResourcePath) -> HandlerResult {
+        match path.id.as_str() {
+            "tt1" => Ok(ResourceResponse::Streams { streams: vec![] }),
+            _ => Err(HandlerError::NotFound),
+        }
+    }
+
+    async fn series_streams(
+        State(ids): State<Vec<String>>,
+        path: ResourcePath,
+        _: RequestContext,
+    ) -> HandlerResult {
+        match ids.contains(&path.id) {
+            true => Ok(ResourceResponse::Streams { streams: vec![] }),
+            false => Err(HandlerError::NotFound),
+        }
+    }
+
+    fn subtitles(path: &ResourcePath) -> HandlerResult {
+        SubtitlesExtra::from_path(path)?;
+        Ok(ResourceResponse::Subtitles { subtitles: vec![] })
+    }
+
+    fn borrowing<'a>(
+        path: &'a ResourcePath,
+        _: &'a RequestContext,
+    ) -> BoxFuture<'a, HandlerResult> {
+        Box::pin(async move {
+            match path.id.as_str() {
+                "tt1" => Ok(ResourceResponse::Streams { streams: vec![] }),
+                _ => Err(HandlerError::NotFound),
+            }
+        })
+    }
+
+    async fn typed_streams(path: ResourcePath) -> Result<Vec<Stream>, HandlerError> {
+        match path.id.as_str() {
+            "tt1" => Ok(vec![]),
+            _ => Err(HandlerError::NotFound),
+        }
+    }
+
+    #[tokio::test]
+    async fn async_and_blocking_functions_are_handlers() {
+        let router = Builder::new(manifest())
+            .with_state(vec!["tt1".to_string()])
+            .handler(HandlerKind::Stream, streams)
+            .handler((HandlerKind::Stream, "series"), series_streams)
+            .handler(HandlerKind::Subtitles, subtitles)
+            .handler(HandlerKind::Meta, |_: &ResourcePath| {
+                Err(HandlerError::NotFound)
+            })
+            .build(ServerOptions::default());
+        let cases = [
+            ("/stream/movie/tt1.json", StatusCode::OK),
+            ("/stream/movie/tt2.json", StatusCode::NOT_FOUND),
+            ("/stream/series/tt1.json", StatusCode::OK),
+            ("/stream/series/tt2.json", StatusCode::NOT_FOUND),
+            ("/subtitles/movie/tt1.json", StatusCode::OK),
+            (
+                "/subtitles/movie/tt1/videoSize=ten.json",
+                StatusCode::BAD_REQUEST,
+            ),
+            ("/meta/movie/tt1.json", StatusCode::NOT_FOUND),
+        ];
+        for (uri, status) in cases {
+            assert_eq!(test_util::status_of(&router, uri).await, status, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn closures_and_borrowing_functions_are_handlers() {
+        let router = Builder::new(manifest())
+            .handler(HandlerKind::Stream, borrowing)
+            .handler(
+                (HandlerKind::Stream, "series"),
+                |path: ResourcePath| async move {
+                    match path.id.as_str() {
+                        "tt1" => Ok(ResourceResponse::Streams { streams: vec![] }),
+                        _ => Err(HandlerError::NotFound),
+                    }
+                },
+            )
+            .handler(
+                HandlerKind::Subtitles,
+                |_: &ResourcePath, ctx: &RequestContext| match ctx.config() {
+                    Some(_) => Err(HandlerError::NotFound),
+                    None => Ok(ResourceResponse::Subtitles { subtitles: vec![] }),
+                },
+            )
+            .handler(HandlerKind::Meta, |_: &ResourcePath| {
+                Err(HandlerError::NotFound)
+            })
+            .build(ServerOptions::default());
+        let cases = [
+            ("/stream/movie/tt1.json", StatusCode::OK),
+            ("/stream/movie/tt2.json", StatusCode::NOT_FOUND),
+            ("/stream/series/tt1.json", StatusCode::OK),
+            ("/stream/series/tt2.json", StatusCode::NOT_FOUND),
+            ("/subtitles/movie/tt1.json", StatusCode::OK),
+        ];
+        for (uri, status) in cases {
+            assert_eq!(test_util::status_of(&router, uri).await, status, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn typed_handlers_answer_with_their_resource() {
+        let router = Builder::new(manifest())
+            .stream(typed_streams)
+            .meta(|_: &ResourcePath| None)
+            .subtitles(|_: ResourcePath| async { vec![] })
+            .build(ServerOptions::default());
+        let cases = [
+            ("/stream/movie/tt1.json", StatusCode::OK),
+            ("/stream/movie/tt2.json", StatusCode::NOT_FOUND),
+            ("/meta/movie/tt1.json", StatusCode::NOT_FOUND),
+            ("/subtitles/movie/tt1.json", StatusCode::OK),
+        ];
+        for (uri, status) in cases {
+            assert_eq!(test_util::status_of(&router, uri).await, status, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn mismatched_response_is_internal_error() {
+        let router = Builder::new(manifest())
+            .handler(HandlerKind::Stream, |_: &ResourcePath| {
+                ResourceResponse::Subtitles { subtitles: vec![] }
+            })
+            .handler(HandlerKind::Meta, |_: &ResourcePath| {
+                Err(HandlerError::NotFound)
+            })
+            .handler(HandlerKind::Subtitles, subtitles)
+            .build(ServerOptions::default());
+        assert_eq!(
+            test_util::status_of(&router, "/stream/movie/tt1.json").await,
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(
+            test_util::status_of(&router, "/subtitles/movie/tt1.json").await,
+            StatusCode::OK
+        );
+    }
+
+    fn meta(r#type: &str, id: &str) -> MetaItem {
+        serde_json::from_value(json!({ "id": id, "type": r#type, "name": id })).unwrap()
+    }
+
+    async fn find_meta(id: &str) -> Option<MetaItem> {
+        (id == "tt1").then(|| meta("movie", id))
+    }
+
+    /// The meta handlers of the getting started example in the README.
+    #[tokio::test]
+    async fn readme_meta_handlers_answer_with_a_meta() {
+        let router = Builder::new(manifest())
+            .handler(HandlerKind::Stream, streams)
+            .handler(HandlerKind::Subtitles, subtitles)
+            .handler(
+                HandlerKind::Meta,
+                |path: ResourcePath, _ctx: RequestContext| async move {
+                    match find_meta(&path.id).await {
+                        Some(meta) => Ok(ResourceResponse::Meta { meta }),
+                        None => Err(HandlerError::NotFound),
+                    }
+                },
+            )
+            .handler((HandlerKind::Meta, "series"), |path: &ResourcePath| {
+                ResourceResponse::Meta {
+                    meta: meta("series", &path.id),
+                }
+            })
+            .build(ServerOptions::default());
+        let cases = [
+            ("/meta/movie/tt1.json", StatusCode::OK),
+            ("/meta/movie/tt2.json", StatusCode::NOT_FOUND),
+            ("/meta/series/tt2.json", StatusCode::OK),
+        ];
+        for (uri, status) in cases {
+            assert_eq!(test_util::status_of(&router, uri).await, status, "{}", uri);
+        }
+    }
+
+    /// The getting started example of the README, with its handlers stubbed.
+    #[test]
+    fn readme_example_builds() {
+        let manifest = ManifestBuilder::new("com.example.addon", "Example")
+            .types(["movie", "series"])
+            .resource("stream")
+            .resource("meta")
+            .resource("subtitles")
+            .build()
+            .unwrap();
+        let top_movies = ManifestCatalog {
+            r#type: "movie".into(),
+            id: "top".into(),
+            name: None,
+            extra: ManifestExtra::Full { props: vec![] },
+        };
+        let result = Builder::new(manifest)
+            .with_state(())
+            .subtitles(|_: State<()>, _: ResourcePath, _: RequestContext| async {
+                Vec::<Subtitles>::new()
+            })
+            .handler(HandlerKind::Stream, streams)
+            .handler((HandlerKind::Stream, "series"), streams)
+            .handler((HandlerKind::Stream, "movie", "tt"), streams)
+            .catalog(top_movies, |_: &ResourcePath| Vec::<MetaItemPreview>::new())
+            .handler(
+                HandlerKind::Meta,
+                |path: ResourcePath, _ctx: RequestContext| async move {
+                    match find_meta(&path.id).await {
+                        Some(meta) => Ok(ResourceResponse::Meta { meta }),
+                        None => Err(HandlerError::NotFound),
+                    }
+                },
+            )
+            .handler((HandlerKind::Meta, "series"), |path: &ResourcePath| {
+                ResourceResponse::Meta {
+                    meta: meta("series", &path.id),
+                }
+            })
+            .try_build(ServerOptions::default());
+        assert!(result.is_ok(), "{:?}", result.err());
+    }
+}
@@ -0,0 +1,174 @@
+use std::error::Error;
+use std::net::IpAddr;
+
+use http::Request;
+use lambda_http::request::RequestContext;
+
+use crate::context::ClientIp;
+use crate::request;
+
+pub(crate) type LambdaRequest = lambda_http::Request;
+pub(crate) type LambdaResponse<T> = lambda_http::Response<T>;
+
+/// Stage of HTTP APIs and function URLs, which API Gateway leaves out of the paths.
+const DEFAULT_STAGE: &str = "$default";
+const REQUEST_ID: &str = "x-request-id";
+
+/// Rebuilds a Lambda request, whose types come from `http` 0.2, as an `http` 1 request. The stage
+/// `lambda_http` puts in front of API Gateway paths is stripped, so that the addon is served at
+/// the root of its stage, the request id of API Gateway is passed on as `X-Request-Id` and its
+/// source IP as the client address. The body is left out since only `GET` requests are answered.
+pub(crate) fn from_lambda(
+    request: &LambdaRequest,
+) -> Result<Request<()>, Box<dyn Error + Send + Sync + 'static>> {
+    let path = strip_stage(request.uri().path(), stage(request));
+    let uri = match request.uri().query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_owned(),
+    };
+    let mut builder = request::from_lambda_parts(request, uri);
+    if let (None, Some(request_id)) = (request.headers().get(REQUEST_ID), request_id(request)) {
+        builder = builder.header(REQUEST_ID, request_id);
+    }
+    let mut rebuilt = builder.body(())?;
+    if let Some(ip) = source_ip(request) {
+        rebuilt.extensions_mut().insert(ClientIp(ip));
+    }
+    Ok(rebuilt)
+}
+
+fn stage(request: &LambdaRequest) -> Option<&str> {
+    let stage = match request.extensions().get::<RequestContext>()? {
+        RequestContext::ApiGatewayV1(ctx) => &ctx.stage,
+        RequestContext::ApiGatewayV2(ctx) => &ctx.stage,
+        _ => return None,
+    };
+    stage.as_deref().filter(|stage| *stage != DEFAULT_STAGE)
+}
+
+fn request_id(request: &LambdaRequest) -> Option<&str> {
+    let request_id = match request.extensions().get::<RequestContext>()? {
+        RequestContext::ApiGatewayV1(ctx) => &ctx.request_id,
+        RequestContext::ApiGatewayV2(ctx) => &ctx.request_id,
+        _ => return None,
+    };
+    request_id.as_deref()
+}
+
+fn source_ip(request: &LambdaRequest) -> Option<IpAddr> {
+    let source_ip = match request.extensions().get::<RequestContext>()? {
+        RequestContext::ApiGatewayV1(ctx) => &ctx.identity.source_ip,
+        RequestContext::ApiGatewayV2(ctx) => &ctx.http.source_ip,
+        _ => return None,
+    };
+    source_ip.as_deref()?.parse().ok()
+}
+
+fn strip_stage<'a>(path: &'a str, stage: Option<&str>) -> &'a str {
+    let rest = stage.and_then(|stage| path.strip_prefix('/')?.strip_prefix(stage));
+    match rest {
+        Some("") => "/",
+        Some(rest) if rest.starts_with('/') => rest,
+        _ => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lambda_http::request::from_str;
+    use lambda_http::Body;
+    use stremio_core::types::addon::ResourcePath;
+    use stremio_core::types::resource::{Stream, StreamSource};
+    use url::Url;
+
+    use crate::builder::Builder;
+    use crate::context::RequestContext;
+    use crate::lambda::strip_stage;
+    use crate::router::Router;
+    use crate::server::{serve_lambda, ServerOptions};
+    use crate::test_util;
+
+    /// Answers with a stream named after the client address and the request id.
+    fn router() -> Router {
+        Builder::new(test_util::stream_manifest())
+            .stream(|_: &ResourcePath, ctx: &RequestContext| {
+                vec![Stream {
+                    source: StreamSource::Url {
+                        url: Url::parse("http://example.com/tt1.mp4").unwrap(),
+                    },
+                    name: ctx
+                        .client_ip()
+                        .map(|ip| format!("{} {}", ip, ctx.request_id())),
+                    description: None,
+                    thumbnail: None,
+                    subtitles: vec![],
+                    behavior_hints: Default::default(),
+                }]
+            })
+            .build(ServerOptions::default())
+    }
+
+    async fn serve(event: &str) -> (u16, String) {
+        let request = from_str(event).unwrap();
+        let response = serve_lambda(router(), request).await.unwrap();
+        let body = match response.body() {
+            Body::Text(text) => text.clone(),
+            body => panic!("unexpected body {:?}", body),
+        };
+        (response.status().as_u16(), body)
+    }
+
+    #[test]
+    fn stage_is_stripped_from_paths() {
+        assert_eq!(
+            strip_stage("/prod/manifest.json", Some("prod")),
+            "/manifest.json"
+        );
+        assert_eq!(strip_stage("/prod", Some("prod")), "/");
+        assert_eq!(
+            strip_stage("/production/manifest.json", Some("prod")),
+            "/production/manifest.json"
+        );
+        assert_eq!(
+            strip_stage("/manifest.json", Some("prod")),
+            "/manifest.json"
+        );
+        assert_eq!(
+            strip_stage("/prod/manifest.json", None),
+            "/prod/manifest.json"
+        );
+    }
+
+    #[tokio::test]
+    async fn api_gateway_rest_requests_are_served() {
+        let (status, body) = serve(include_str!("../fixtures/lambda/apigw_v1_request.json")).await;
+        assert_eq!(status, 200);
+        assert!(
+            body.contains(r#""name":"203.0.113.7 c6af9ac6-7b61-11e6-9a41-93e8deadbeef""#),
+            "{}",
+            body
+        );
+    }
+
+    #[tokio::test]
+    async fn api_gateway_http_requests_are_served() {
+        let (status, body) = serve(include_str!("../fixtures/lambda/apigw_v2_request.json")).await;
+        assert_eq!(status, 200);
+        assert!(
+            body.contains(r#""name":"203.0.113.7 NfNhDh8WliAEMJw=""#),
+            "{}",
+            body
+        );
+    }
+
+    #[tokio::test]
+    async fn function_url_requests_are_served() {
+        let (status, body) =
+            serve(include_str!("../fixtures/lambda/function_url_request.json")).await;
+        assert_eq!(status, 200);
+        assert_eq!(
+            body,
+            serde_json::to_string(&test_util::stream_manifest()).unwrap()
+        );
+    }
+}
@@ -1,12 +1,42 @@
 pub use futures;
+pub use http;
+#[cfg(feature = "server-hyper")]
 pub use hyper;
+#[cfg(feature = "lambda")]
+pub use lambda_http;
 pub use stremio_core;
+#[cfg(feature = "tower")]
+pub use tower;
 pub use url;
+#[cfg(feature = "serverless-vercel")]
 pub use vercel_runtime;
 
+pub mod addon;
+#[cfg(feature = "axum")]
+pub mod axum;
 pub mod builder;
+#[cfg(any(feature = "cgi", feature = "fastcgi"))]
+mod cgi;
+pub mod config;
+pub mod context;
+pub mod error;
+pub mod extra;
+#[cfg(feature = "fastcgi")]
+mod fastcgi;
+pub mod handler;
+#[cfg(feature = "lambda")]
+mod lambda;
+pub mod manifest;
+pub mod middleware;
+pub mod parser;
+#[cfg(any(feature = "serverless-vercel", feature = "lambda"))]
 mod request;
 mod response;
 pub mod router;
 pub mod server;
+#[cfg(feature = "tower")]
+mod service;
+pub mod state;
+#[cfg(test)]
+mod test_util;
 pub mod utils;
@@ -0,0 +1,534 @@
+use stremio_core::types::addon::{
+    Manifest, ManifestBehaviorHints, ManifestCatalog, ManifestExtra, ManifestResource,
+    OptionsLimit, Version,
+};
+use url::Url;
+
+use crate::error::{BuildError, ValidationIssue};
+use crate::utils;
+
+/// Builds a `Manifest` field by field and validates it once it is complete.
+///
+/// When `types` is not set, it is collected from the catalogs and resources.
+///
+/// ```no_run
+/// use stremio_addon_sdk::manifest::ManifestBuilder;
+///
+/// let manifest = ManifestBuilder::new("com.example.movies", "Movies")
+///     .types(["movie"])
+///     .resource("stream")
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ManifestBuilder {
+    manifest: Manifest,
+    types_set: bool,
+}
+
+impl ManifestBuilder {
+    pub fn new(id: impl Into<String>, name: impl Into<String>) -> Self {
+        Self {
+            manifest: Manifest {
+                id: id.into(),
+                version: Version::new(0, 1, 0),
+                name: name.into(),
+                contact_email: None,
+                description: None,
+                logo: None,
+                background: None,
+                types: vec![],
+                resources: vec![],
+                id_prefixes: None,
+                catalogs: vec![],
+                addon_catalogs: vec![],
+                behavior_hints: ManifestBehaviorHints::default(),
+            },
+            types_set: false,
+        }
+    }
+
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.manifest.id = id.into();
+        self
+    }
+
+    pub fn version(mut self, version: Version) -> Self {
+        self.manifest.version = version;
+        self
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.manifest.name = name.into();
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.manifest.description = Some(description.into());
+        self
+    }
+
+    pub fn contact_email(mut self, contact_email: impl Into<String>) -> Self {
+        self.manifest.contact_email = Some(contact_email.into());
+        self
+    }
+
+    pub fn logo(mut self, logo: Url) -> Self {
+        self.manifest.logo = Some(logo);
+        self
+    }
+
+    pub fn background(mut self, background: Url) -> Self {
+        self.manifest.background = Some(background);
+        self
+    }
+
+    pub fn types<I, T>(mut self, types: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.manifest.types = types.into_iter().map(Into::into).collect();
+        self.types_set = true;
+        self
+    }
+
+    /// Declares a resource served for every type and id prefix of the manifest.
+    pub fn resource(mut self, name: impl Into<String>) -> Self {
+        self.manifest
+            .resources
+            .push(ManifestResource::Short(name.into()));
+        self
+    }
+
+    /// Declares a resource with its own types and id prefixes.
+    pub fn full_resource<I, T>(
+        mut self,
+        name: impl Into<String>,
+        types: I,
+        id_prefixes: Option<Vec<String>>,
+    ) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.manifest.resources.push(ManifestResource::Full {
+            name: name.into(),
+            types: Some(types.into_iter().map(Into::into).collect()),
+            id_prefixes,
+        });
+        self
+    }
+
+    pub fn id_prefixes<I, T>(mut self, id_prefixes: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.manifest.id_prefixes = Some(id_prefixes.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn catalog(mut self, catalog: ManifestCatalog) -> Self {
+        self.manifest.catalogs.push(catalog);
+        self
+    }
+
+    pub fn addon_catalog(mut self, catalog: ManifestCatalog) -> Self {
+        self.manifest.addon_catalogs.push(catalog);
+        self
+    }
+
+    pub fn adult(mut self, adult: bool) -> Self {
+        self.manifest.behavior_hints.adult = adult;
+        self
+    }
+
+    pub fn p2p(mut self, p2p: bool) -> Self {
+        self.manifest.behavior_hints.p2p = p2p;
+        self
+    }
+
+    pub fn configurable(mut self, configurable: bool) -> Self {
+        self.manifest.behavior_hints.configurable = configurable;
+        self
+    }
+
+    pub fn configuration_required(mut self, configuration_required: bool) -> Self {
+        self.manifest.behavior_hints.configuration_required = configuration_required;
+        self
+    }
+
+    /// Returns the manifest, or every issue found by `validate_manifest`.
+    pub fn build(self) -> Result<Manifest, BuildError> {
+        let mut manifest = self.manifest;
+        if !self.types_set {
+            let catalog_types = manifest.catalogs.iter().map(|catalog| &catalog.r#type);
+            let resource_types = manifest
+                .resources
+                .iter()
+                .filter_map(|resource| match resource {
+                    ManifestResource::Full {
+                        types: Some(types), ..
+                    } => Some(types),
+                    _ => None,
+                })
+                .flatten();
+            for r#type in catalog_types.chain(resource_types) {
+                if !manifest.types.contains(r#type) {
+                    manifest.types.push(r#type.clone());
+                }
+            }
+        }
+        let issues = validate_manifest(&manifest);
+        if !issues.is_empty() {
+            return Err(BuildError::new(issues));
+        }
+        Ok(manifest)
+    }
+}
+
+/// Checks the manifest against the rules Stremio applies when installing an addon and reports
+/// every violation found.
+pub fn validate_manifest(manifest: &Manifest) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+    if !is_reverse_domain(&manifest.id) {
+        issues.push(invalid(
+            "id",
+            format!(
+                "'{}' is not in reverse domain notation, e.g. 'com.example.addon'",
+                manifest.id
+            ),
+        ));
+    }
+    if manifest.name.trim().is_empty() {
+        issues.push(invalid("name", "must not be empty"));
+    }
+    for resource in &manifest.resources {
+        if let ManifestResource::Full { name, types, .. } = resource {
+            let declared = types.as_ref().unwrap_or(&manifest.types);
+            if declared.is_empty() {
+                issues.push(invalid(
+                    format!("resources[{}]", name),
+                    "has no types, neither its own nor manifest.types",
+                ));
+            }
+        } else if manifest.types.is_empty() {
+            issues.push(invalid(
+                format!("resources[{}]", utils::resource_name(resource)),
+                "has no types, as manifest.types is empty",
+            ));
+        }
+    }
+    validate_catalogs(manifest, "catalogs", &manifest.catalogs, true, &mut issues);
+    validate_catalogs(
+        manifest,
+        "addonCatalogs",
+        &manifest.addon_catalogs,
+        false,
+        &mut issues,
+    );
+    issues
+}
+
+fn validate_catalogs(
+    manifest: &Manifest,
+    field: &str,
+    catalogs: &[ManifestCatalog],
+    check_types: bool,
+    issues: &mut Vec<ValidationIssue>,
+) {
+    for (index, catalog) in catalogs.iter().enumerate() {
+        let field = format!("{}[{}/{}]", field, catalog.r#type, catalog.id);
+        if catalog.r#type.is_empty() || catalog.id.is_empty() {
+            issues.push(invalid(&field, "must have a non-empty type and id"));
+        }
+        if check_types && !manifest.types.contains(&catalog.r#type) {
+            issues.push(invalid(
+                &field,
+                format!(
+                    "has type '{}', which manifest.types does not contain",
+                    catalog.r#type
+                ),
+            ));
+        }
+        let duplicate = catalogs[..index]
+            .iter()
+            .any(|other| other.r#type == catalog.r#type && other.id == catalog.id);
+        if duplicate {
+            issues.push(invalid(&field, "is declared more than once"));
+        }
+        let names = match &catalog.extra {
+            ManifestExtra::Full { props } => {
+                for prop in props {
+                    if prop.options_limit != OptionsLimit::default() && prop.options.is_empty() {
+                        issues.push(invalid(
+                            format!("{}.extra[{}]", field, prop.name),
+                            "sets optionsLimit, but has no options",
+                        ));
+                    }
+                }
+                props.iter().map(|prop| &prop.name).collect::<Vec<_>>()
+            }
+            ManifestExtra::Short { supported, .. } => supported.iter().collect(),
+        };
+        for (index, name) in names.iter().enumerate() {
+            if names[..index].contains(name) {
+                issues.push(invalid(
+                    format!("{}.extra[{}]", field, name),
+                    "is declared more than once",
+                ));
+            }
+        }
+    }
+}
+
+fn invalid(field: impl Into<String>, reason: impl Into<String>) -> ValidationIssue {
+    ValidationIssue::InvalidManifest {
+        field: field.into(),
+        reason: reason.into(),
+    }
+}
+
+fn is_reverse_domain(id: &str) -> bool {
+    let segments = id.split('.').collect::<Vec<_>>();
+    segments.len() >= 2
+        && segments.iter().all(|segment| {
+            !segment.is_empty()
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use stremio_core::types::addon::{
+        ExtraProp, Manifest, ManifestCatalog, ManifestExtra, ManifestResource, OptionsLimit,
+    };
+
+    use crate::error::ValidationIssue;
+    use crate::manifest::{validate_manifest, ManifestBuilder};
+    use crate::test_util;
+    use crate::utils;
+
+    fn catalog(r#type: &str, id: &str, props: Vec<ExtraProp>) -> ManifestCatalog {
+        ManifestCatalog {
+            r#type: r#type.into(),
+            id: id.into(),
+            name: Some(id.into()),
+            extra: ManifestExtra::Full { props },
+        }
+    }
+
+    fn prop(name: &str) -> ExtraProp {
+        ExtraProp {
+            name: name.into(),
+            is_required: false,
+            options: vec![],
+            options_limit: OptionsLimit::default(),
+        }
+    }
+
+    fn fields(manifest: &Manifest) -> Vec<String> {
+        validate_manifest(manifest)
+            .into_iter()
+            .map(|issue| match issue {
+                ValidationIssue::InvalidManifest { field, .. } => field,
+                issue => panic!("unexpected issue: {}", issue),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn default_manifest_needs_id_and_name() {
+        assert_eq!(fields(&utils::default_manifest()), ["id", "name"]);
+        assert!(validate_manifest(&test_util::manifest()).is_empty());
+    }
+
+    #[test]
+    fn id_must_be_reverse_domain() {
+        for id in ["", "addon", "com..addon", ".com.addon", "com.my addon"] {
+            let manifest = Manifest {
+                id: id.into(),
+                ..test_util::manifest()
+            };
+            assert_eq!(fields(&manifest), ["id"], "{}", id);
+        }
+        let manifest = Manifest {
+            id: "com.example.my-addon_2".into(),
+            ..test_util::manifest()
+        };
+        assert!(fields(&manifest).is_empty());
+    }
+
+    #[test]
+    fn name_must_not_be_empty() {
+        let manifest = Manifest {
+            name: " ".into(),
+            ..test_util::manifest()
+        };
+        assert_eq!(fields(&manifest), ["name"]);
+    }
+
+    #[test]
+    fn catalog_types_must_be_declared() {
+        let manifest = Manifest {
+            types: vec!["movie".into()],
+            catalogs: vec![
+                catalog("movie", "top", vec![]),
+                catalog("series", "top", vec![]),
+            ],
+            ..test_util::manifest()
+        };
+        assert_eq!(fields(&manifest), ["catalogs[series/top]"]);
+    }
+
+    #[test]
+    fn catalogs_must_have_type_and_id() {
+        let manifest = Manifest {
+            types: vec!["movie".into()],
+            catalogs: vec![catalog("movie", "", vec![])],
+            ..test_util::manifest()
+        };
+        assert_eq!(fields(&manifest), ["catalogs[movie/]"]);
+    }
+
+    #[test]
+    fn catalogs_must_be_unique() {
+        let manifest = Manifest {
+            types: vec!["movie".into()],
+            catalogs: vec![
+                catalog("movie", "top", vec![]),
+                catalog("movie", "top", vec![]),
+            ],
+            addon_catalogs: vec![
+                catalog("other", "community", vec![]),
+                catalog("other", "community", vec![]),
+            ],
+            ..test_util::manifest()
+        };
+        assert_eq!(
+            fields(&manifest),
+            ["catalogs[movie/top]", "addonCatalogs[other/community]"]
+        );
+    }
+
+    #[test]
+    fn extra_names_must_be_unique() {
+        let manifest = Manifest {
+            types: vec!["movie".into()],
+            catalogs: vec![
+                catalog(
+                    "movie",
+                    "top",
+                    vec![prop("genre"), prop("skip"), prop("genre")],
+                ),
+                ManifestCatalog {
+                    extra: ManifestExtra::Short {
+                        required: vec![],
+                        supported: vec!["search".into(), "search".into()],
+                    },
+                    ..catalog("movie", "search", vec![])
+                },
+            ],
+            ..test_util::manifest()
+        };
+        assert_eq!(
+            fields(&manifest),
+            [
+                "catalogs[movie/top].extra[genre]",
+                "catalogs[movie/search].extra[search]"
+            ]
+        );
+    }
+
+    #[test]
+    fn options_limit_requires_options() {
+        let manifest = Manifest {
+            types: vec!["movie".into()],
+            catalogs: vec![catalog(
+                "movie",
+                "top",
+                vec![
+                    ExtraProp {
+                        options_limit: OptionsLimit(2),
+                        ..prop("genre")
+                    },
+                    ExtraProp {
+                        options: vec!["Action".into(), "Drama".into()],
+                        options_limit: OptionsLimit(2),
+                        ..prop("year")
+                    },
+                ],
+            )],
+            ..test_util::manifest()
+        };
+        assert_eq!(fields(&manifest), ["catalogs[movie/top].extra[genre]"]);
+    }
+
+    #[test]
+    fn resources_must_have_types() {
+        let manifest = Manifest {
+            resources: vec![
+                ManifestResource::Short("stream".into()),
+                ManifestResource::Full {
+                    name: "meta".into(),
+                    types: Some(vec!["movie".into()]),
+                    id_prefixes: None,
+                },
+            ],
+            ..test_util::manifest()
+        };
+        assert_eq!(fields(&manifest), ["resources[stream]"]);
+    }
+
+    #[test]
+    fn reports_every_violation() {
+        let manifest = Manifest {
+            id: "addon".into(),
+            name: "".into(),
+            catalogs: vec![catalog("movie", "top", vec![])],
+            ..test_util::manifest()
+        };
+        assert_eq!(fields(&manifest), ["id", "name", "catalogs[movie/top]"]);
+    }
+
+    #[test]
+    fn manifest_builder_collects_types() {
+        let manifest = ManifestBuilder::new("com.example.addon", "Example")
+            .full_resource("stream", ["movie", "series"], Some(vec!["tt".into()]))
+            .catalog(catalog("channel", "top", vec![]))
+            .catalog(catalog("movie", "top", vec![]))
+            .configurable(true)
+            .build()
+            .unwrap();
+        assert_eq!(manifest.types, ["channel", "movie", "series"]);
+        assert!(manifest.behavior_hints.configurable);
+        assert!(manifest.addon_catalogs.is_empty());
+    }
+
+    #[test]
+    fn manifest_builder_keeps_explicit_types() {
+        let manifest = ManifestBuilder::new("com.example.addon", "Example")
+            .types(["movie"])
+            .resource("stream")
+            .build()
+            .unwrap();
+        assert_eq!(manifest.types, ["movie"]);
+        assert_eq!(
+            manifest.resources,
+            [ManifestResource::Short("stream".into())]
+        );
+    }
+
+    #[test]
+    fn manifest_builder_validates() {
+        let err = ManifestBuilder::new("example", "")
+            .resource("stream")
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(err.issues().len(), 3);
+    }
+}
@@ -0,0 +1,285 @@
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use stremio_core::types::addon::ResourcePath;
+
+use crate::builder::HandlerFn;
+use crate::context::RequestContext;
+use crate::error::HandlerResult;
+
+/// Logic running around the handlers, registered with `Builder::middleware`.
+///
+/// A middleware receives the path and context of a request once its handler is found. It can
+/// change them before calling [`Next::run`], answer without calling it, or change what it
+/// returns. Middlewares run in the order they are registered, the first one being the outermost.
+///
+/// ```no_run
+/// # use stremio_addon_sdk::context::RequestContext;
+/// # use stremio_addon_sdk::error::HandlerResult;
+/// # use stremio_addon_sdk::futures::future::BoxFuture;
+/// # use stremio_addon_sdk::middleware::{Middleware, Next};
+/// # use stremio_addon_sdk::stremio_core::types::addon::{ResourcePath, ResourceResponse};
+/// # use stremio_addon_sdk::stremio_core::types::resource::MetaItemPreview;
+/// # fn is_adult(meta: &MetaItemPreview) -> bool { unimplemented!() }
+/// struct HideAdult;
+///
+/// impl Middleware for HideAdult {
+///     fn call<'a>(
+///         &'a self,
+///         path: ResourcePath,
+///         ctx: RequestContext,
+///         next: Next<'a>,
+///     ) -> BoxFuture<'a, HandlerResult> {
+///         Box::pin(async move {
+///             match next.run(path, ctx).await? {
+///                 ResourceResponse::Metas { metas } => Ok(ResourceResponse::Metas {
+///                     metas: metas.into_iter().filter(|meta| !is_adult(meta)).collect(),
+///                 }),
+///                 response => Ok(response),
+///             }
+///         })
+///     }
+/// }
+/// ```
+pub trait Middleware: Send + Sync + 'static {
+    fn call<'a>(
+        &'a self,
+        path: ResourcePath,
+        ctx: RequestContext,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, HandlerResult>;
+}
+
+/// The middlewares left to run, followed by the handler.
+pub struct Next<'a> {
+    middlewares: &'a [Arc<dyn Middleware>],
+    handler: &'a HandlerFn,
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(middlewares: &'a [Arc<dyn Middleware>], handler: &'a HandlerFn) -> Self {
+        Self {
+            middlewares,
+            handler,
+        }
+    }
+
+    /// Runs the rest of the chain on the given path and context.
+    pub async fn run(self, path: ResourcePath, ctx: RequestContext) -> HandlerResult {
+        match self.middlewares.split_first() {
+            Some((middleware, middlewares)) => {
+                let next = Next::new(middlewares, self.handler);
+                middleware.call(path, ctx, next).await
+            }
+            None => (self.handler)(&path, &ctx).await,
+        }
+    }
+}
+
+/// Middleware calling a closure.
+pub struct FromFn<F>(F);
+
+/// Turns a closure into a [`Middleware`]:
+///
+/// ```no_run
+/// # use std::time::Instant;
+/// # use stremio_addon_sdk::builder::Builder;
+/// # use stremio_addon_sdk::middleware;
+/// # use stremio_addon_sdk::stremio_core::types::addon::Manifest;
+/// # fn builder(manifest: Manifest) -> Builder {
+/// Builder::new(manifest).middleware(middleware::from_fn(|path, ctx, next| {
+///     Box::pin(async move {
+///         let start = Instant::now();
+///         let result = next.run(path, ctx).await;
+///         log::info!("answered in {:?}", start.elapsed());
+///         result
+///     })
+/// }))
+/// # }
+/// ```
+pub fn from_fn<F>(middleware: F) -> FromFn<F>
+where
+    F: for<'a> Fn(ResourcePath, RequestContext, Next<'a>) -> BoxFuture<'a, HandlerResult>
+        + Send
+        + Sync
+        + 'static,
+{
+    FromFn(middleware)
+}
+
+impl<F> Middleware for FromFn<F>
+where
+    F: for<'a> Fn(ResourcePath, RequestContext, Next<'a>) -> BoxFuture<'a, HandlerResult>
+        + Send
+        + Sync
+        + 'static,
+{
+    fn call<'a>(
+        &'a self,
+        path: ResourcePath,
+        ctx: RequestContext,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, HandlerResult> {
+        (self.0)(path, ctx, next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use futures::future::BoxFuture;
+    use http::{Request, StatusCode};
+    use stremio_core::types::addon::{ResourcePath, ResourceResponse};
+    use stremio_core::types::resource::{Stream, StreamSource};
+    use url::Url;
+    #[cfg(feature = "serverless-vercel")]
+    use vercel_runtime::Body;
+
+    use crate::builder::Builder;
+    use crate::context::RequestContext;
+    use crate::error::{HandlerError, HandlerResult};
+    use crate::middleware::{self, Middleware, Next};
+    #[cfg(feature = "serverless-vercel")]
+    use crate::request::ServerlessRequest;
+    use crate::router::Router;
+    #[cfg(feature = "serverless-vercel")]
+    use crate::server::serve_serverless;
+    use crate::server::ServerOptions;
+    use crate::test_util;
+
+    fn stream(url: &str) -> Stream {
+        Stream {
+            source: StreamSource::Url {
+                url: Url::parse(url).unwrap(),
+            },
+            name: None,
+            description: None,
+            thumbnail: None,
+            subtitles: vec![],
+            behavior_hints: Default::default(),
+        }
+    }
+
+    /// Answers with a stream whose url ends with the requested id.
+    async fn streams(path: ResourcePath) -> Vec<Stream> {
+        vec![stream(&format!("http://example.com/{}", path.id))]
+    }
+
+    async fn body_of(router: &Router, request: Request<()>) -> String {
+        let response = router.handle(request).await;
+        String::from_utf8(response.into_body().to_vec()).unwrap()
+    }
+
+    fn get(path: &str) -> Request<()> {
+        Request::builder()
+            .uri(format!("http://127.0.0.1:7070{}", path))
+            .body(())
+            .unwrap()
+    }
+
+    /// Records when it runs, and appends its name to the names of the streams.
+    struct Record {
+        name: &'static str,
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Middleware for Record {
+        fn call<'a>(
+            &'a self,
+            path: ResourcePath,
+            ctx: RequestContext,
+            next: Next<'a>,
+        ) -> BoxFuture<'a, HandlerResult> {
+            Box::pin(async move {
+                self.calls
+                    .lock()
+                    .unwrap()
+                    .push(format!("{} before", self.name));
+                let result = next.run(path, ctx).await;
+                self.calls
+                    .lock()
+                    .unwrap()
+                    .push(format!("{} after", self.name));
+                match result? {
+                    ResourceResponse::Streams { streams } => Ok(ResourceResponse::Streams {
+                        streams: streams
+                            .into_iter()
+                            .map(|stream| Stream {
+                                name: Some(match stream.name {
+                                    Some(name) => format!("{} {}", name, self.name),
+                                    None => self.name.into(),
+                                }),
+                                ..stream
+                            })
+                            .collect(),
+                    }),
+                    response => Ok(response),
+                }
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn middlewares_run_in_registration_order() {
+        let calls = Arc::new(Mutex::new(vec![]));
+        let router = Builder::new(test_util::stream_manifest())
+            .middleware(Record {
+                name: "outer",
+                calls: calls.clone(),
+            })
+            .middleware(Record {
+                name: "inner",
+                calls: calls.clone(),
+            })
+            .stream(streams)
+            .build(ServerOptions::default());
+        let body = body_of(&router, get("/stream/movie/tt1.json")).await;
+        assert!(body.contains(r#""name":"inner outer""#), "{}", body);
+        assert_eq!(
+            *calls.lock().unwrap(),
+            ["outer before", "inner before", "inner after", "outer after"]
+        );
+    }
+
+    #[tokio::test]
+    async fn middleware_can_rewrite_path_and_short_circuit() {
+        let router = Builder::new(test_util::stream_manifest())
+            .middleware(middleware::from_fn(|mut path, ctx, next| {
+                Box::pin(async move {
+                    if ctx.header("authorization") != Some("secret") {
+                        return Err(HandlerError::NotFound);
+                    }
+                    path.id = path.id.to_uppercase();
+                    next.run(path, ctx).await
+                })
+            }))
+            .stream(streams)
+            .build(ServerOptions::default());
+        let mut request = get("/stream/movie/tt1.json");
+        request
+            .headers_mut()
+            .insert("authorization", "secret".parse().unwrap());
+        let body = body_of(&router, request).await;
+        assert!(body.contains("http://example.com/TT1"), "{}", body);
+        let response = router.handle(get("/stream/movie/tt1.json")).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[cfg(feature = "serverless-vercel")]
+    #[tokio::test]
+    async fn middlewares_apply_to_serverless_requests() {
+        let router = Builder::new(test_util::stream_manifest())
+            .middleware(middleware::from_fn(|_, _, _| {
+                Box::pin(async { Err(HandlerError::NotFound) })
+            }))
+            .stream(streams)
+            .build(ServerOptions::default());
+        let mut serverless = ServerlessRequest::new(Body::Empty);
+        *serverless.uri_mut() = "http://127.0.0.1:7070/stream/movie/tt1.json"
+            .parse()
+            .unwrap();
+        let response = serve_serverless(router, serverless).await.unwrap();
+        assert_eq!(response.status(), 404);
+    }
+}
@@ -0,0 +1,263 @@
+use std::fmt::{Display, Formatter};
+
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use stremio_core::types::addon::{ExtraValue, ResourcePath};
+
+/// Characters left as they are by `encodeURIComponent`, which Stremio uses to build request paths.
+const COMPONENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'!')
+    .remove(b'~')
+    .remove(b'*')
+    .remove(b'\'')
+    .remove(b'(')
+    .remove(b')');
+
+const JSON_SUFFIX: &str = ".json";
+
+/// Reason a request path is not a valid resource path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathError {
+    MissingLeadingSlash,
+    MissingJsonSuffix,
+    /// The path has this many segments instead of 3 or 4.
+    SegmentCount(usize),
+    EmptySegment,
+    /// A `%` is not followed by two hex digits.
+    InvalidEscape,
+    /// The percent-decoded bytes are not UTF-8.
+    InvalidUtf8,
+    /// An extra has no `=`.
+    MissingExtraValue(String),
+    EmptyExtraName,
+}
+
+impl std::error::Error for PathError {}
+
+impl Display for PathError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PathError::MissingLeadingSlash => write!(f, "path must start with '/'"),
+            PathError::MissingJsonSuffix => write!(f, "path must end with '.json'"),
+            PathError::SegmentCount(count) => {
+                write!(f, "path must have 3 or 4 segments, but it has {}", count)
+            }
+            PathError::EmptySegment => write!(f, "path segments must not be empty"),
+            PathError::InvalidEscape => write!(f, "'%' must be followed by two hex digits"),
+            PathError::InvalidUtf8 => write!(f, "percent-decoded path is not valid UTF-8"),
+            PathError::MissingExtraValue(name) => write!(f, "extra '{}' has no value", name),
+            PathError::EmptyExtraName => write!(f, "extra names must not be empty"),
+        }
+    }
+}
+
+/// Parses `/{resource}/{type}/{id}.json` or `/{resource}/{type}/{id}/{extra}.json`, where the
+/// extra is `name=value` pairs joined by `&`. Every component is percent-decoded and `+` in the
+/// extra stands for a space.
+///
+/// `parse_resource_path(&format_resource_path(&path))` gives back `path` for every path whose
+/// resource, type, id and extra names are not empty.
+pub fn parse_resource_path(path: &str) -> Result<ResourcePath, PathError> {
+    let path = path
+        .strip_prefix('/')
+        .ok_or(PathError::MissingLeadingSlash)?
+        .strip_suffix(JSON_SUFFIX)
+        .ok_or(PathError::MissingJsonSuffix)?;
+    let segments = path.split('/').collect::<Vec<_>>();
+    if segments.len() < 3 || segments.len() > 4 {
+        return Err(PathError::SegmentCount(segments.len()));
+    }
+    if segments.iter().any(|segment| segment.is_empty()) {
+        return Err(PathError::EmptySegment);
+    }
+    let resource = decode(segments[0])?;
+    let r#type = decode(segments[1])?;
+    let id = decode(segments[2])?;
+    match segments.get(3) {
+        Some(extra) => Ok(ResourcePath::with_extra(
+            &resource,
+            &r#type,
+            &id,
+            &parse_extra(extra)?,
+        )),
+        None => Ok(ResourcePath::without_extra(&resource, &r#type, &id)),
+    }
+}
+
+/// Formats a resource path the way Stremio requests it.
+pub fn format_resource_path(path: &ResourcePath) -> String {
+    let mut formatted = format!(
+        "/{}/{}/{}",
+        encode(&path.resource),
+        encode(&path.r#type),
+        encode(&path.id)
+    );
+    if !path.extra.is_empty() {
+        let extra = path
+            .extra
+            .iter()
+            .map(|extra| format!("{}={}", encode(&extra.name), encode(&extra.value)))
+            .collect::<Vec<_>>()
+            .join("&");
+        formatted.push('/');
+        formatted.push_str(&extra);
+    }
+    formatted.push_str(JSON_SUFFIX);
+    formatted
+}
+
+fn parse_extra(extra: &str) -> Result<Vec<ExtraValue>, PathError> {
+    extra
+        .split('&')
+        .map(|pair| {
+            let pair = pair.replace('+', " ");
+            let (name, value) = pair
+                .split_once('=')
+                .ok_or_else(|| PathError::MissingExtraValue(pair.clone()))?;
+            if name.is_empty() {
+                return Err(PathError::EmptyExtraName);
+            }
+            Ok(ExtraValue {
+                name: decode(name)?,
+                value: decode(value)?,
+            })
+        })
+        .collect()
+}
+
+fn decode(component: &str) -> Result<String, PathError> {
+    let bytes = component.as_bytes();
+    for (index, _) in bytes.iter().enumerate().filter(|(_, &byte)| byte == b'%') {
+        let escape = bytes.get(index + 1..index + 3);
+        if !escape.is_some_and(|escape| escape.iter().all(u8::is_ascii_hexdigit)) {
+            return Err(PathError::InvalidEscape);
+        }
+    }
+    percent_decode_str(component)
+        .decode_utf8()
+        .map(|decoded| decoded.into_owned())
+        .map_err(|_| PathError::InvalidUtf8)
+}
+
+fn encode(component: &str) -> String {
+    utf8_percent_encode(component, COMPONENT).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use stremio_core::types::addon::{ExtraValue, ResourcePath};
+
+    use crate::parser::{format_resource_path, parse_resource_path, PathError};
+
+    fn extra(name: &str, value: &str) -> ExtraValue {
+        ExtraValue {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+
+    #[test]
+    fn parses_path_without_extra() {
+        assert_eq!(
+            parse_resource_path("/meta/movie/tt1254207.json"),
+            Ok(ResourcePath::without_extra("meta", "movie", "tt1254207"))
+        );
+    }
+
+    #[test]
+    fn parses_and_decodes_extra() {
+        assert_eq!(
+            parse_resource_path("/catalog/series/top/search=the%20office&genre=Sci+Fi.json"),
+            Ok(ResourcePath::with_extra(
+                "catalog",
+                "series",
+                "top",
+                &[extra("search", "the office"), extra("genre", "Sci Fi")]
+            ))
+        );
+    }
+
+    #[test]
+    fn keeps_equal_signs_in_extra_values() {
+        assert_eq!(
+            parse_resource_path("/stream/movie/tt1/token=YWJj==&skip=0.json"),
+            Ok(ResourcePath::with_extra(
+                "stream",
+                "movie",
+                "tt1",
+                &[extra("token", "YWJj=="), extra("skip", "0")]
+            ))
+        );
+    }
+
+    #[test]
+    fn strips_json_suffix_only_at_the_end() {
+        assert_eq!(
+            parse_resource_path("/meta/other/file.json.backup.json"),
+            Ok(ResourcePath::without_extra(
+                "meta",
+                "other",
+                "file.json.backup"
+            ))
+        );
+    }
+
+    #[test]
+    fn decodes_id() {
+        assert_eq!(
+            parse_resource_path("/meta/series/kitsu%3A1%2F2.json"),
+            Ok(ResourcePath::without_extra("meta", "series", "kitsu:1/2"))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_paths() {
+        let cases = [
+            ("meta/movie/tt1.json", PathError::MissingLeadingSlash),
+            ("/meta/movie/tt1", PathError::MissingJsonSuffix),
+            ("/meta/movie.json", PathError::SegmentCount(2)),
+            ("/a/b/c/d/e.json", PathError::SegmentCount(5)),
+            ("/meta//tt1.json", PathError::EmptySegment),
+            ("/meta/movie/tt%1.json", PathError::InvalidEscape),
+            ("/meta/movie/tt%.json", PathError::InvalidEscape),
+            ("/meta/movie/tt%FF.json", PathError::InvalidUtf8),
+            (
+                "/catalog/movie/top/genre=a&skip.json",
+                PathError::MissingExtraValue("skip".into()),
+            ),
+            ("/catalog/movie/top/=a.json", PathError::EmptyExtraName),
+            (
+                "/catalog/movie/top/genre=a&.json",
+                PathError::MissingExtraValue("".into()),
+            ),
+        ];
+        for (path, error) in cases {
+            assert_eq!(parse_resource_path(path), Err(error), "{}", path);
+        }
+    }
+
+    #[test]
+    fn formats_what_it_parses() {
+        let paths = [
+            ResourcePath::without_extra("meta", "movie", "tt1254207"),
+            ResourcePath::without_extra("meta", "other", "a b/c?d#e%f+g.json"),
+            ResourcePath::with_extra(
+                "catalog",
+                "series",
+                "top",
+                &[
+                    extra("search", "the office & co"),
+                    extra("token", "a+b=c"),
+                    extra("genre", ""),
+                    extra("ключ", "значение"),
+                ],
+            ),
+        ];
+        for path in paths {
+            let formatted = format_resource_path(&path);
+            assert_eq!(parse_resource_path(&formatted), Ok(path), "{}", formatted);
+        }
+    }
+}
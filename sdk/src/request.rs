@@ -1,27 +1,44 @@
-use std::str::FromStr;
+#[cfg(feature = "serverless-vercel")]
+use std::error::Error;
 
-use hyper::{Method, Uri};
+use http::{request, Request};
+#[cfg(feature = "lambda")]
+use lambda_http::Request as LambdaRequest;
+#[cfg(not(feature = "lambda"))]
+use vercel_runtime::Request as LambdaRequest;
 
-pub(crate) type HyperRequest<T> = hyper::Request<T>;
-pub(crate) type ServerlessRequest = vercel_runtime::Request;
+#[cfg(feature = "serverless-vercel")]
+use crate::context::{self, ClientIp};
 
-pub(crate) enum Request<T> {
-    Hyper(HyperRequest<T>),
-    Serverless(ServerlessRequest),
-}
+#[cfg(feature = "serverless-vercel")]
+pub(crate) type ServerlessRequest = vercel_runtime::Request;
 
-impl<T> Request<T> {
-    pub(crate) fn method(&self) -> Method {
-        match self {
-            Request::Hyper(req) => req.method().clone(),
-            Request::Serverless(req) => Method::from_str(req.method().as_str()).unwrap(),
-        }
+/// Starts an `http` 1 request for `uri` with the method and headers of a request of Lambda or
+/// Vercel, whose types come from `http` 0.2 through `lambda_http`.
+pub(crate) fn from_lambda_parts(request: &LambdaRequest, uri: String) -> request::Builder {
+    let mut builder = Request::builder()
+        .method(request.method().as_str())
+        .uri(uri);
+    for (name, value) in request.headers() {
+        builder = builder.header(name.as_str(), value.as_bytes());
     }
+    builder
+}
 
-    pub(crate) fn uri(&self) -> Uri {
-        match self {
-            Request::Hyper(req) => req.uri().clone(),
-            Request::Serverless(req) => req.uri().to_string().parse::<Uri>().unwrap(),
-        }
+/// Rebuilds a Vercel request as an `http` 1 request. The body is left out since only `GET`
+/// requests are answered.
+///
+/// Unlike other servers, which only trust `X-Forwarded-For` with
+/// `ServerOptions::trust_forwarded_for` as they cannot tell whether a proxy set it, a Vercel
+/// function is always reached through Vercel's edge. It overwrites the header with the address of
+/// the client, so the header is taken as the client address the platform gives.
+#[cfg(feature = "serverless-vercel")]
+pub(crate) fn from_serverless(
+    request: &ServerlessRequest,
+) -> Result<Request<()>, Box<dyn Error + Send + Sync + 'static>> {
+    let mut rebuilt = from_lambda_parts(request, request.uri().to_string()).body(())?;
+    if let Some(ip) = context::forwarded_for(rebuilt.headers()) {
+        rebuilt.extensions_mut().insert(ClientIp(ip));
     }
+    Ok(rebuilt)
 }
@@ -1,32 +1,36 @@
 use std::error::Error;
 
-use hyper::{HeaderMap, StatusCode};
+#[cfg(any(
+    feature = "server-hyper",
+    feature = "tower",
+    feature = "serverless-vercel",
+    feature = "lambda"
+))]
+use bytes::Bytes;
+use http::{HeaderMap, Response, StatusCode};
+#[cfg(feature = "lambda")]
+use lambda_http::{Body, Response as LambdaResponse};
+#[cfg(all(feature = "serverless-vercel", not(feature = "lambda")))]
+use vercel_runtime::{Body, Response as LambdaResponse};
 
-pub(crate) type HyperResponse<T> = hyper::Response<T>;
+#[cfg(feature = "serverless-vercel")]
 pub(crate) type ServerlessResponse<T> = vercel_runtime::Response<T>;
 
-pub(crate) enum Response<T> {
-    Hyper(HyperResponse<T>),
-    Serverless(ServerlessResponse<T>),
+pub(crate) struct ResponseBuilder<T> {
+    code: StatusCode,
+    headers: HeaderMap,
+    body: Option<T>,
 }
 
-impl<T> Response<T> {
-    pub(crate) fn builder() -> ResponseBuilder<T> {
+impl<T> ResponseBuilder<T> {
+    pub(crate) fn new() -> ResponseBuilder<T> {
         ResponseBuilder {
             code: StatusCode::OK,
             headers: HeaderMap::new(),
             body: None,
         }
     }
-}
-
-pub(crate) struct ResponseBuilder<T> {
-    code: StatusCode,
-    headers: HeaderMap,
-    body: Option<T>,
-}
 
-impl<T> ResponseBuilder<T> {
     pub(crate) fn status(self, code: StatusCode) -> ResponseBuilder<T> {
         Self { code, ..self }
     }
@@ -42,45 +46,41 @@ impl<T> ResponseBuilder<T> {
         }
     }
 
-    pub(crate) fn build(
-        self,
-        is_serverless: bool,
-    ) -> Result<Response<T>, Box<dyn Error + Send + Sync + 'static>>
+    pub(crate) fn build(self) -> Result<Response<T>, Box<dyn Error + Send + Sync + 'static>>
     where
         T: Default,
     {
-        if is_serverless {
-            self.build_serverless()
-        } else {
-            self.build_hyper()
-        }
-    }
-
-    fn build_hyper(self) -> Result<Response<T>, Box<dyn Error + Send + Sync + 'static>>
-    where
-        T: Default,
-    {
-        let mut builder = HyperResponse::builder().status(self.code);
+        let mut builder = Response::builder().status(self.code);
         for (name, value) in &self.headers {
             builder = builder.header(name.as_str(), value.as_bytes());
         }
         builder
             .body(self.body.unwrap_or_default())
-            .map(Response::Hyper)
             .map_err(|err| err.into())
     }
+}
 
-    fn build_serverless(self) -> Result<Response<T>, Box<dyn Error + Send + Sync + 'static>>
-    where
-        T: Default,
-    {
-        let mut builder = ServerlessResponse::builder().status(self.code.as_str());
-        for (name, value) in &self.headers {
-            builder = builder.header(name.as_str(), value.as_bytes());
-        }
-        builder
-            .body(self.body.unwrap_or_default())
-            .map(Response::Serverless)
-            .map_err(|err| err.into())
+/// The router only answers with JSON, HTML or plain text, so its bodies are valid UTF-8.
+#[cfg(any(feature = "server-hyper", feature = "tower"))]
+pub(crate) fn into_text(response: Response<Bytes>) -> Response<String> {
+    response.map(|body| String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Rebuilds a response with the `http` 0.2 types of `lambda_http`, which Vercel's runtime is built
+/// on too, so that Lambda and Vercel answer alike.
+#[cfg(any(feature = "serverless-vercel", feature = "lambda"))]
+pub(crate) fn into_lambda(
+    response: Response<Bytes>,
+) -> Result<LambdaResponse<Body>, Box<dyn Error + Send + Sync + 'static>> {
+    let (parts, body) = response.into_parts();
+    let mut builder = LambdaResponse::builder().status(parts.status.as_u16());
+    for (name, value) in &parts.headers {
+        builder = builder.header(name.as_str(), value.as_bytes());
     }
+    // binary bodies are sent base64 encoded, text ones as they are
+    let body = match String::from_utf8(body.to_vec()) {
+        Ok(text) => Body::Text(text),
+        Err(err) => Body::Binary(err.into_bytes()),
+    };
+    builder.body(body).map_err(|err| err.into())
 }
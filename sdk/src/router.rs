@@ -1,14 +1,30 @@
 use std::fmt::{Debug, Display, Formatter};
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
 
-use hyper::{header, HeaderMap, Method, StatusCode};
-use hyper::header::HeaderValue;
-use stremio_core::constants::ADDON_MANIFEST_PATH;
-use stremio_core::types::addon::{ExtraValue, Manifest, ResourcePath};
+use futures::FutureExt;
 
-use crate::builder::Handler;
-use crate::request::Request;
-use crate::response::Response;
+use bytes::Bytes;
+use http::header::HeaderValue;
+use http::request::Parts;
+use http::{header, HeaderMap, Method, Request, Response, StatusCode};
+use serde_json::json;
+use stremio_core::constants::{
+    ADDON_MANIFEST_PATH, CATALOG_RESOURCE_NAME, META_RESOURCE_NAME, STREAM_RESOURCE_NAME,
+    SUBTITLES_RESOURCE_NAME,
+};
+use stremio_core::types::addon::{Manifest, ManifestCatalog, ResourcePath, ResourceResponse};
+
+use crate::builder::{Handler, ADDON_CATALOG_RESOURCE_NAME};
+use crate::config::UserConfig;
+use crate::context::RequestContext;
+use crate::error::{HandlerError, HandlerResult, ValidationIssue};
+use crate::extra;
+use crate::middleware::{Middleware, Next};
+use crate::parser;
+use crate::response::ResponseBuilder;
 use crate::server::ServerOptions;
+use crate::utils;
 
 type Result<T> = std::result::Result<T, Error>;
 
@@ -36,95 +52,168 @@ impl Display for Error {
     }
 }
 
-enum ResponseKind {
+pub(crate) enum ResponseKind {
     Json(String),
     Html(String),
-    BadRequest,
+    BadRequest(String),
     NotFound,
     MethodNotAllowed,
-    Manifest,
+    Manifest { configured: bool },
+    Error(HandlerError),
 }
 
+/// Cheap to clone: its parts are shared, since servers such as tower's clone it for every request.
 #[derive(Clone)]
 pub struct Router {
-    manifest: Manifest,
-    handlers: Vec<Handler>,
-    options: ServerOptions,
+    manifest: Arc<Manifest>,
+    handlers: Arc<[Handler]>,
+    middlewares: Arc<[Arc<dyn Middleware>]>,
+    options: Arc<ServerOptions>,
+    warnings: Arc<[ValidationIssue]>,
 }
 
 impl Router {
     pub(crate) fn new(manifest: Manifest, handlers: Vec<Handler>, options: ServerOptions) -> Self {
         Self {
-            manifest,
-            handlers,
-            options,
+            manifest: Arc::new(manifest),
+            handlers: handlers.into(),
+            middlewares: Arc::new([]),
+            options: Arc::new(options),
+            warnings: Arc::new([]),
         }
     }
 
-    pub(crate) async fn route<T, E>(&self, request: Request<E>) -> Result<Response<T>>
-    where
-        T: From<String> + Default,
-    {
-        let is_serverless = match request {
-            Request::Serverless(_) => true,
-            Request::Hyper(_) => false,
-        };
-        if request.method() != Method::GET {
-            return self.response_from(is_serverless, ResponseKind::MethodNotAllowed);
+    pub(crate) fn with_middlewares(mut self, middlewares: Vec<Arc<dyn Middleware>>) -> Self {
+        self.middlewares = middlewares.into();
+        self
+    }
+
+    pub(crate) fn with_warnings(mut self, warnings: Vec<ValidationIssue>) -> Self {
+        self.warnings = warnings.into();
+        self
+    }
+
+    /// Warnings validation found when the router was built, which did not keep it from being
+    /// built. `Builder::build` logs them, `Builder::try_build` leaves them to the caller.
+    pub fn warnings(&self) -> &[ValidationIssue] {
+        &self.warnings
+    }
+
+    /// Answers a request, whichever server received it. The body is never read since only `GET`
+    /// requests are answered. A response that cannot be built is answered with an empty 500.
+    ///
+    /// ```no_run
+    /// # use stremio_addon_sdk::http::Request;
+    /// # use stremio_addon_sdk::router::Router;
+    /// # async fn answer(router: Router) -> Result<(), stremio_addon_sdk::http::Error> {
+    /// let response = router.handle(Request::get("/manifest.json").body(())?).await;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn handle<B>(&self, request: Request<B>) -> Response<Bytes> {
+        let (parts, _) = request.into_parts();
+        or_internal_error(self.route(&parts).await, &parts.uri)
+    }
+
+    async fn route(&self, request: &Parts) -> Result<Response<Bytes>> {
+        if request.method != Method::GET {
+            return self.response_from(ResponseKind::MethodNotAllowed);
         }
-        return match request.uri().path() {
-            "/" => self.response_from(
-                is_serverless,
-                ResponseKind::Html(self.options.index_html.clone()),
-            ),
-            ADDON_MANIFEST_PATH => self.response_from(is_serverless, ResponseKind::Manifest),
+        match request.uri.path() {
+            "/" => self.response_from(ResponseKind::Html(self.options.index_html.clone())),
+            ADDON_MANIFEST_PATH => self.response_from(ResponseKind::Manifest { configured: false }),
             p => {
-                let parts = p.split('/').skip(1).collect::<Vec<&str>>();
-                if parts.len() < 3 || parts.len() > 4 {
-                    return self.response_from(is_serverless, ResponseKind::BadRequest);
+                let (config, p) = self.split_config(p);
+                let config = match config.map(UserConfig::from_segment) {
+                    Some(None) => {
+                        return self
+                            .response_from(ResponseKind::BadRequest("invalid user config".into()))
+                    }
+                    Some(config) => config,
+                    None => None,
+                };
+                if p == ADDON_MANIFEST_PATH {
+                    return self.response_from(ResponseKind::Manifest {
+                        configured: config.is_some(),
+                    });
                 }
-                let path = if parts.len() == 4 {
-                    let extras = parts[3]
-                        .replace(".json", "")
-                        .split('&')
-                        .map_while(|part| {
-                            let extra = part.split('=').collect::<Vec<&str>>();
-                            if extra.len() == 2 {
-                                Some(ExtraValue {
-                                    name: extra[0].to_string(),
-                                    value: extra[1].to_string(),
-                                })
-                            } else {
-                                None
-                            }
-                        })
-                        .collect::<Vec<ExtraValue>>();
-                    ResourcePath::with_extra(parts[0], parts[1], parts[2], extras.as_slice())
-                } else {
-                    ResourcePath::without_extra(
-                        parts[0],
-                        parts[1],
-                        parts[2].replace(".json", "").as_str(),
-                    )
+                let path = match parser::parse_resource_path(p) {
+                    Ok(path) => path,
+                    Err(err) => {
+                        return self.response_from(ResponseKind::BadRequest(err.to_string()))
+                    }
                 };
                 let handler = self
                     .handlers
                     .iter()
-                    .find(|&handler| p.starts_with(format!("/{}", handler.name).as_str()));
-                if handler.is_none() {
-                    return self.response_from(is_serverless, ResponseKind::NotFound);
+                    .filter(|&handler| handler.matches(&path))
+                    .max_by_key(|&handler| handler.specificity());
+                if handler.is_none() || !self.is_supported(&path) {
+                    return self.response_from(ResponseKind::NotFound);
                 }
-                let resource = (handler.unwrap().func)(&path)
+                if let Some(catalog) = self.declared_catalog(&path) {
+                    if let Err(reason) = extra::check_declared(catalog, &path.extra) {
+                        return self.response_from(ResponseKind::BadRequest(reason));
+                    }
+                }
+                let func = &handler.unwrap().func;
+                let ctx = RequestContext::from_parts(request, config, &self.options);
+                // middlewares and handler run inside the future so that panics while creating their
+                // futures are caught too
+                let next = Next::new(&self.middlewares, func.as_ref());
+                let result = AssertUnwindSafe(next.run(path.clone(), ctx.clone()))
+                    .catch_unwind()
                     .await
-                    .map(|path| serde_json::to_string(&path).map_err(Error::Serde));
-                if resource.is_none() {
-                    return self.response_from(is_serverless, ResponseKind::NotFound);
+                    .unwrap_or_else(|panic| {
+                        log::error!(
+                            "handler panicked for {:?} (request {}): {}",
+                            path,
+                            ctx.request_id(),
+                            utils::panic_message(panic.as_ref())
+                        );
+                        Err(HandlerError::Internal)
+                    });
+                self.response_to(&path, &ctx, result)
+            }
+        }
+    }
+
+    /// Answers with what the handler of a resource returned.
+    pub(crate) fn response_to(
+        &self,
+        path: &ResourcePath,
+        ctx: &RequestContext,
+        result: HandlerResult,
+    ) -> Result<Response<Bytes>> {
+        match result {
+            Ok(resource) if response_resource(&resource) != path.resource => {
+                log::error!(
+                    "handler for {:?} (request {}) answered with a {} response",
+                    path,
+                    ctx.request_id(),
+                    response_resource(&resource)
+                );
+                self.response_from(ResponseKind::Error(HandlerError::Internal))
+            }
+            Ok(resource) => {
+                let json = serde_json::to_string(&resource).map_err(Error::Serde)?;
+                self.response_from(ResponseKind::Json(json))
+            }
+            Err(err) => {
+                if let HandlerError::Upstream(_) = err {
+                    log::error!(
+                        "handler error for {:?} (request {}): {}",
+                        path,
+                        ctx.request_id(),
+                        err
+                    );
                 }
-                self.response_from(is_serverless, ResponseKind::Json(resource.unwrap()?))
+                self.response_from(ResponseKind::Error(err))
             }
-        };
+        }
     }
 
+    #[cfg(any(feature = "server-hyper", feature = "axum"))]
     pub(crate) fn options(&self) -> &ServerOptions {
         &self.options
     }
@@ -133,44 +222,105 @@ impl Router {
         &self.manifest
     }
 
-    fn response_from<T>(&self, is_serverless: bool, kind: ResponseKind) -> Result<Response<T>>
-    where
-        T: From<String> + Default,
-    {
+    /// Whether the name is the one of a served or declared resource.
+    fn is_resource(&self, name: &str) -> bool {
+        let manifest = self.manifest();
+        self.handlers.iter().any(|handler| handler.name == name)
+            || utils::resource_declaration(manifest, name).is_some()
+            || (name == CATALOG_RESOURCE_NAME && !manifest.catalogs.is_empty())
+            || (name == ADDON_CATALOG_RESOURCE_NAME && !manifest.addon_catalogs.is_empty())
+    }
+
+    /// Splits the user config segment off a path, which is there when a manifest or resource path
+    /// follows a leading segment that is not a resource name. The resource after it is not
+    /// checked, so that a configured path to an unknown resource is answered with a 404.
+    pub(crate) fn split_config<'p>(&self, path: &'p str) -> (Option<&'p str>, &'p str) {
+        let parts = path.split('/').skip(1).collect::<Vec<&str>>();
+        let config = match parts.as_slice() {
+            [config, "manifest.json"] => Some(*config),
+            [config, ..] if parts.len() == 4 || parts.len() == 5 => Some(*config),
+            _ => None,
+        }
+        // like the segments of resource paths, the config segment must not be empty
+        .filter(|config| !config.is_empty() && !self.is_resource(config));
+        match config {
+            Some(config) => (Some(config), &path[1 + config.len()..]),
+            None => (None, path),
+        }
+    }
+
+    /// The catalog a catalog or addon catalog path refers to.
+    pub(crate) fn declared_catalog(&self, path: &ResourcePath) -> Option<&ManifestCatalog> {
+        let manifest = self.manifest();
+        let catalogs = match path.resource.as_str() {
+            CATALOG_RESOURCE_NAME => &manifest.catalogs,
+            ADDON_CATALOG_RESOURCE_NAME => &manifest.addon_catalogs,
+            _ => return None,
+        };
+        catalogs
+            .iter()
+            .find(|catalog| catalog.r#type == path.r#type && catalog.id == path.id)
+    }
+
+    /// Checks the path against the types and id prefixes the manifest declares for its resource.
+    pub(crate) fn is_supported(&self, path: &ResourcePath) -> bool {
+        let manifest = self.manifest();
+        if path.resource == CATALOG_RESOURCE_NAME || path.resource == ADDON_CATALOG_RESOURCE_NAME {
+            return self.declared_catalog(path).is_some();
+        }
+        let Some((types, id_prefixes)) = utils::resource_declaration(manifest, &path.resource)
+        else {
+            return false;
+        };
+        types.contains(&path.r#type)
+            && id_prefixes.map_or(true, |prefixes| {
+                prefixes.iter().any(|prefix| path.id.starts_with(prefix))
+            })
+    }
+
+    pub(crate) fn response_from(&self, kind: ResponseKind) -> Result<Response<Bytes>> {
         let headers = self.header_map_from(&kind);
         let code = match &kind {
-            ResponseKind::Manifest | ResponseKind::Html(_) | ResponseKind::Json(_) => {
+            ResponseKind::Manifest { .. } | ResponseKind::Html(_) | ResponseKind::Json(_) => {
                 StatusCode::OK
             }
-            ResponseKind::BadRequest => StatusCode::BAD_REQUEST,
+            ResponseKind::BadRequest(_) => StatusCode::BAD_REQUEST,
             ResponseKind::NotFound => StatusCode::NOT_FOUND,
             ResponseKind::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            ResponseKind::Error(err) => err.status(),
         };
-        let manifest = if matches!(kind, ResponseKind::Manifest) {
-            Some(serde_json::to_string(self.manifest()).map_err(Error::Serde)?)
+        let manifest = if let ResponseKind::Manifest { configured } = kind {
+            let mut manifest = self.manifest().clone();
+            // an installed configuration satisfies the requirement, so Stremio should not ask again
+            if configured {
+                manifest.behavior_hints.configuration_required = false;
+            }
+            Some(serde_json::to_string(&manifest).map_err(Error::Serde)?)
         } else {
             None
         };
         let body = match kind {
-            ResponseKind::Json(str) => T::from(str),
-            ResponseKind::Html(str) => T::from(str),
-            ResponseKind::MethodNotAllowed => T::from("Method Not Allowed".into()),
-            ResponseKind::NotFound => T::from("Not Found".into()),
-            ResponseKind::BadRequest => T::from("Bad Request".into()),
-            ResponseKind::Manifest => T::from(manifest.unwrap()),
+            ResponseKind::Json(str) => str,
+            ResponseKind::Html(str) => str,
+            ResponseKind::Manifest { .. } => manifest.unwrap(),
+            ResponseKind::MethodNotAllowed => "Method Not Allowed".into(),
+            // the router's own 400s and 404s are answered like those of handlers
+            ResponseKind::BadRequest(reason) => error_body(&HandlerError::BadRequest(reason)),
+            ResponseKind::NotFound => error_body(&HandlerError::NotFound),
+            ResponseKind::Error(err) => error_body(&err),
         };
-        Response::builder()
+        ResponseBuilder::new()
             .status(code)
             .headers(headers)
-            .body(body)
-            .build(is_serverless)
+            .body(Bytes::from(body))
+            .build()
             .map_err(Error::Http)
     }
 
     fn header_map_from(&self, kind: &ResponseKind) -> HeaderMap {
         let mut headers_map = HeaderMap::new();
         match kind {
-            ResponseKind::Manifest | ResponseKind::Json(_) => {
+            ResponseKind::Manifest { .. } | ResponseKind::Json(_) => {
                 headers_map.append(
                     header::ACCESS_CONTROL_ALLOW_ORIGIN,
                     HeaderValue::from_static("*"),
@@ -190,61 +340,166 @@ impl Router {
             ResponseKind::Html(_) => {
                 headers_map.append(header::CONTENT_TYPE, HeaderValue::from_static("text/html"));
             }
-            _ => (),
+            // errors must not be cached like resources, an outage would outlive its fix
+            ResponseKind::Error(_) | ResponseKind::BadRequest(_) | ResponseKind::NotFound => {
+                headers_map.append(
+                    header::ACCESS_CONTROL_ALLOW_ORIGIN,
+                    HeaderValue::from_static("*"),
+                );
+                headers_map.append(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("application/json"),
+                );
+            }
+            ResponseKind::MethodNotAllowed => (),
         };
+        if let ResponseKind::Error(HandlerError::RateLimited {
+            retry_after: Some(retry_after),
+        }) = kind
+        {
+            headers_map.append(
+                header::RETRY_AFTER,
+                HeaderValue::from(retry_after.as_secs()),
+            );
+        }
         headers_map
     }
 }
 
+fn error_body(err: &HandlerError) -> String {
+    json!({ "error": err.public_message() }).to_string()
+}
+
+/// Answers with an empty 500 when a response could not be built.
+pub(crate) fn or_internal_error(
+    response: Result<Response<Bytes>>,
+    request: impl Display,
+) -> Response<Bytes> {
+    response.unwrap_or_else(|err| {
+        log::error!("failed to answer {}: {}", request, err);
+        let mut response = Response::new(Bytes::new());
+        *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+        response
+    })
+}
+
+/// Resource whose requests a response answers.
+fn response_resource(response: &ResourceResponse) -> &'static str {
+    match response {
+        ResourceResponse::Metas { .. } | ResourceResponse::MetasDetailed { .. } => {
+            CATALOG_RESOURCE_NAME
+        }
+        ResourceResponse::Meta { .. } => META_RESOURCE_NAME,
+        ResourceResponse::Streams { .. } => STREAM_RESOURCE_NAME,
+        ResourceResponse::Subtitles { .. } => SUBTITLES_RESOURCE_NAME,
+        ResourceResponse::Addons { .. } => ADDON_CATALOG_RESOURCE_NAME,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::future;
+    use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::Arc;
+    use std::time::Duration;
 
-    use hyper::{header, Request, StatusCode};
-    use hyper::http::HeaderValue;
-    use stremio_core::types::addon::ResourcePath;
+    use http::HeaderValue;
+    use http::{header, Request, StatusCode};
+    use stremio_core::types::addon::{
+        Manifest, ManifestBehaviorHints, ManifestCatalog, ManifestExtra, ManifestResource,
+        ResourcePath, ResourceResponse,
+    };
+    use stremio_core::types::resource::{Stream, StreamSource};
+    #[cfg(feature = "serverless-vercel")]
+    use vercel_runtime::Body;
 
-    use crate::builder::Handler;
-    use crate::request;
-    use crate::response::Response;
+    use crate::builder::{Builder, Handler};
+    use crate::context::RequestContext;
+    use crate::error::HandlerError;
+    use crate::extra::{CatalogExtra, FromExtra};
+    #[cfg(feature = "serverless-vercel")]
+    use crate::request::ServerlessRequest;
     use crate::router::Router;
+    #[cfg(feature = "serverless-vercel")]
+    use crate::server::serve_serverless;
     use crate::server::ServerOptions;
-    use crate::utils::default_manifest;
+    use crate::state::State;
+    use crate::test_util;
+
+    fn streams_handler(name: &str) -> Handler {
+        Handler {
+            name: name.into(),
+            r#type: None,
+            id_prefix: None,
+            id: None,
+            func: Arc::new(|_: &ResourcePath, _: &RequestContext| {
+                Box::pin(future::ready(Ok(ResourceResponse::Streams {
+                    streams: vec![],
+                })))
+            }),
+        }
+    }
+
+    fn metas_handler() -> Handler {
+        Handler {
+            func: Arc::new(|_: &ResourcePath, _: &RequestContext| {
+                Box::pin(future::ready(Ok(ResourceResponse::Metas { metas: vec![] })))
+            }),
+            ..streams_handler("catalog")
+        }
+    }
+
+    fn bound_handler(r#type: Option<&str>, id_prefix: Option<&str>, name: &'static str) -> Handler {
+        Handler {
+            name: "stream".into(),
+            r#type: r#type.map(Into::into),
+            id_prefix: id_prefix.map(Into::into),
+            id: None,
+            func: Arc::new(move |_: &ResourcePath, _: &RequestContext| {
+                Box::pin(future::ready(Ok(ResourceResponse::Streams {
+                    streams: vec![Stream {
+                        source: StreamSource::Url {
+                            url: "http://127.0.0.1/".parse().unwrap(),
+                        },
+                        name: Some(name.into()),
+                        description: None,
+                        thumbnail: None,
+                        subtitles: vec![],
+                        behavior_hints: Default::default(),
+                    }],
+                })))
+            }),
+        }
+    }
+
+    async fn body_of(router: &Router, uri: &str) -> String {
+        let response = router
+            .handle(Request::builder().uri(uri).body(()).unwrap())
+            .await;
+        String::from_utf8(response.into_body().to_vec()).unwrap()
+    }
 
     #[tokio::test]
     async fn response_kind_method_not_allowed_when_not_get() {
-        let router = Router::new(default_manifest(), vec![], ServerOptions::default());
+        let router = Router::new(test_util::manifest(), vec![], ServerOptions::default());
         let response = router
-            .route::<String, ()>(request::Request::Hyper(
-                Request::builder().method("POST").body(()).unwrap(),
-            ))
+            .handle(Request::builder().method("POST").body(()).unwrap())
             .await;
-        assert!(response.is_ok());
-        let response = match response.unwrap() {
-            Response::Hyper(res) => res,
-            Response::Serverless(_) => unreachable!(),
-        };
         assert!(response.headers().is_empty());
         assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
     }
 
     #[tokio::test]
     async fn response_kind_html_when_initial_path() {
-        let router = Router::new(default_manifest(), vec![], ServerOptions::default());
+        let router = Router::new(test_util::manifest(), vec![], ServerOptions::default());
         let response = router
-            .route::<String, ()>(request::Request::Hyper(
+            .handle(
                 Request::builder()
                     .uri("http://127.0.0.1:7070/")
                     .body(())
                     .unwrap(),
-            ))
+            )
             .await;
-        assert!(response.is_ok());
-        let response = match response.unwrap() {
-            Response::Hyper(res) => res,
-            Response::Serverless(_) => unreachable!(),
-        };
         assert_eq!(
             response.headers().get(header::CONTENT_TYPE).unwrap(),
             HeaderValue::from_static("text/html")
@@ -253,67 +508,65 @@ mod tests {
 
     #[tokio::test]
     async fn response_kind_json_when_manifest_path() {
-        let router = Router::new(default_manifest(), vec![], ServerOptions::default());
+        let router = Router::new(test_util::manifest(), vec![], ServerOptions::default());
         let response = router
-            .route::<String, ()>(request::Request::Hyper(
+            .handle(
                 Request::builder()
                     .uri("http://127.0.0.1:7070/manifest.json")
                     .body(())
                     .unwrap(),
-            ))
+            )
             .await;
-        assert!(response.is_ok());
-        let response = match response.unwrap() {
-            Response::Hyper(res) => res,
-            Response::Serverless(_) => unreachable!(),
-        };
         assert_eq!(
             response.headers().get(header::CONTENT_TYPE).unwrap(),
             HeaderValue::from_static("application/json")
         );
         assert_eq!(
             response.body(),
-            &serde_json::to_string(&default_manifest()).unwrap()
+            &serde_json::to_string(&test_util::manifest()).unwrap()
         );
     }
 
     #[tokio::test]
     async fn response_kind_bad_request_when_invalid_path() {
-        let router = Router::new(default_manifest(), vec![], ServerOptions::default());
+        let router = Router::new(test_util::manifest(), vec![], ServerOptions::default());
         let response = router
-            .route::<String, ()>(request::Request::Hyper(
+            .handle(
                 Request::builder()
                     .uri("http://127.0.0.1:7070/foo/bar")
                     .body(())
                     .unwrap(),
-            ))
+            )
             .await;
-        assert!(response.is_ok());
-        let response = match response.unwrap() {
-            Response::Hyper(res) => res,
-            Response::Serverless(_) => unreachable!(),
-        };
-        assert!(response.headers().is_empty());
+        // answered like the 400s of handlers, and not cached
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE).unwrap(),
+            HeaderValue::from_static("application/json")
+        );
+        assert!(response.headers().get(header::CACHE_CONTROL).is_none());
         assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = String::from_utf8(response.into_body().to_vec()).unwrap();
+        assert!(body.starts_with(r#"{"error":"bad request: "#), "{}", body);
     }
 
     #[tokio::test]
     async fn response_kind_not_found_when_no_handler() {
-        let router = Router::new(default_manifest(), vec![], ServerOptions::default());
+        let router = Router::new(test_util::manifest(), vec![], ServerOptions::default());
         let response = router
-            .route::<String, ()>(request::Request::Hyper(
+            .handle(
                 Request::builder()
-                    .uri("http://127.0.0.1:7070/stream/movie/id")
+                    .uri("http://127.0.0.1:7070/stream/movie/id.json")
                     .body(())
                     .unwrap(),
-            ))
+            )
             .await;
-        assert!(response.is_ok());
-        let response = match response.unwrap() {
-            Response::Hyper(res) => res,
-            Response::Serverless(_) => unreachable!(),
-        };
-        assert!(response.headers().is_empty());
+        // answered like the 404s of handlers, and not cached
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE).unwrap(),
+            HeaderValue::from_static("application/json")
+        );
+        assert!(response.headers().get(header::CACHE_CONTROL).is_none());
+        assert_eq!(response.body(), r#"{"error":"not found"}"#);
         assert_eq!(response.status(), StatusCode::NOT_FOUND);
     }
 
@@ -321,23 +574,769 @@ mod tests {
     async fn response_kind_not_found_when_no_resource() {
         let handler = Handler {
             name: "stream".into(),
-            func: Arc::new(|_: &ResourcePath| Box::pin(future::ready(None))),
+            r#type: None,
+            id_prefix: None,
+            id: None,
+            func: Arc::new(|_: &ResourcePath, _: &RequestContext| {
+                Box::pin(future::ready(Err(HandlerError::NotFound)))
+            }),
         };
-        let router = Router::new(default_manifest(), vec![handler], ServerOptions::default());
+        let router = Router::new(
+            test_util::stream_manifest(),
+            vec![handler],
+            ServerOptions::default(),
+        );
         let response = router
-            .route::<String, ()>(request::Request::Hyper(
+            .handle(
                 Request::builder()
                     .uri("http://127.0.0.1:7070/stream/movie/id.json")
                     .body(())
                     .unwrap(),
-            ))
+            )
             .await;
-        assert!(response.is_ok());
-        let response = match response.unwrap() {
-            Response::Hyper(res) => res,
-            Response::Serverless(_) => unreachable!(),
-        };
-        assert!(response.headers().is_empty());
+        assert!(response.headers().get(header::CACHE_CONTROL).is_none());
+        assert_eq!(response.body(), r#"{"error":"not found"}"#);
         assert_eq!(response.status(), StatusCode::NOT_FOUND);
     }
+
+    #[tokio::test]
+    async fn response_kind_json_when_configured_manifest_path() {
+        let manifest = Manifest {
+            behavior_hints: ManifestBehaviorHints {
+                configurable: true,
+                configuration_required: true,
+                ..Default::default()
+            },
+            ..test_util::manifest()
+        };
+        let router = Router::new(manifest.clone(), vec![], ServerOptions::default());
+        let response = router
+            .handle(
+                Request::builder()
+                    .uri("http://127.0.0.1:7070/%7B%22key%22%3A%22value%22%7D/manifest.json")
+                    .body(())
+                    .unwrap(),
+            )
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let served = serde_json::from_slice::<Manifest>(response.body()).unwrap();
+        assert!(served.behavior_hints.configurable);
+        assert!(!served.behavior_hints.configuration_required);
+    }
+
+    #[tokio::test]
+    async fn response_kind_bad_request_when_invalid_config() {
+        let router = Router::new(test_util::manifest(), vec![], ServerOptions::default());
+        let response = router
+            .handle(
+                Request::builder()
+                    .uri("http://127.0.0.1:7070/%FF%FE/manifest.json")
+                    .body(())
+                    .unwrap(),
+            )
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn config_segment_is_checked_like_resource_segments() {
+        let router = test_util::stream_router();
+        // a resource name or an empty segment is not a config
+        for uri in [
+            "http://127.0.0.1:7070/stream/manifest.json",
+            "http://127.0.0.1:7070//manifest.json",
+            "http://127.0.0.1:7070//stream/movie/tt1.json",
+        ] {
+            assert_eq!(
+                test_util::status_of(&router, uri).await,
+                StatusCode::BAD_REQUEST,
+                "{}",
+                uri
+            );
+        }
+        assert_eq!(
+            test_util::status_of(&router, "http://127.0.0.1:7070/stream/movie/tt1/x=1.json").await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn response_kind_not_found_when_configured_path_to_unknown_resource() {
+        let router = test_util::stream_router();
+        for uri in [
+            "http://127.0.0.1:7070/%7B%22token%22%3A%22abc%22%7D/meta/movie/tt1.json",
+            "http://127.0.0.1:7070/%7B%22token%22%3A%22abc%22%7D/foo/movie/tt1/x=1.json",
+        ] {
+            assert_eq!(
+                test_util::status_of(&router, uri).await,
+                StatusCode::NOT_FOUND,
+                "{}",
+                uri
+            );
+        }
+        assert_eq!(
+            test_util::status_of(
+                &router,
+                "http://127.0.0.1:7070/%7B%22token%22%3A%22abc%22%7D/stream/movie/tt1.json"
+            )
+            .await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn handler_receives_config_which_is_not_json() {
+        let handler = Handler {
+            func: Arc::new(|_: &ResourcePath, ctx: &RequestContext| {
+                let config = ctx.config().unwrap();
+                assert_eq!(config.raw(), "debrid=rd|quality=4k");
+                assert!(config.value().is_none());
+                assert!(config.deserialize::<serde_json::Value>().is_err());
+                Box::pin(future::ready(Ok(ResourceResponse::Streams {
+                    streams: vec![],
+                })))
+            }),
+            ..streams_handler("stream")
+        };
+        let router = Router::new(
+            test_util::stream_manifest(),
+            vec![handler],
+            ServerOptions::default(),
+        );
+        for uri in [
+            "http://127.0.0.1:7070/debrid=rd%7Cquality=4k/manifest.json",
+            "http://127.0.0.1:7070/debrid=rd%7Cquality=4k/stream/movie/tt1.json",
+        ] {
+            assert_eq!(test_util::status_of(&router, uri).await, StatusCode::OK);
+        }
+    }
+
+    #[tokio::test]
+    async fn handler_receives_config_when_configured_path() {
+        let handler = Handler {
+            name: "stream".into(),
+            r#type: None,
+            id_prefix: None,
+            id: None,
+            func: Arc::new(|path: &ResourcePath, ctx: &RequestContext| {
+                assert_eq!(path.r#type, "movie");
+                assert_eq!(path.id, "tt1254207");
+                assert_eq!(path.extra.len(), 1);
+                let token = ctx.config().and_then(|config| config.get("token")).cloned();
+                assert_eq!(token, Some("abc".into()));
+                Box::pin(future::ready(Ok(ResourceResponse::Streams {
+                    streams: vec![],
+                })))
+            }),
+        };
+        let router = Router::new(
+            test_util::stream_manifest(),
+            vec![handler],
+            ServerOptions::default(),
+        );
+        let response = router
+            .handle(Request::builder()
+                    .uri("http://127.0.0.1:7070/%7B%22token%22%3A%22abc%22%7D/stream/movie/tt1254207/foo=bar.json")
+                    .body(())
+                    .unwrap())
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn handler_receives_decoded_extra() {
+        let handler = Handler {
+            func: Arc::new(|path: &ResourcePath, _: &RequestContext| {
+                assert_eq!(path.id, "kitsu:1");
+                assert_eq!(
+                    path.get_extra_first_value("search"),
+                    Some(&"the office".into())
+                );
+                assert_eq!(path.get_extra_first_value("token"), Some(&"YWJj==".into()));
+                Box::pin(future::ready(Ok(ResourceResponse::Streams {
+                    streams: vec![],
+                })))
+            }),
+            ..streams_handler("stream")
+        };
+        let router = Router::new(
+            test_util::stream_manifest(),
+            vec![handler],
+            ServerOptions::default(),
+        );
+        let status = test_util::status_of(
+            &router,
+            "http://127.0.0.1:7070/stream/movie/kitsu%3A1/search=the%20office&token=YWJj==.json",
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn response_kind_bad_request_with_reason_when_malformed_extra() {
+        let router = test_util::stream_router();
+        let uri = "http://127.0.0.1:7070/stream/movie/tt1/genre=a&skip.json";
+        assert_eq!(
+            test_util::status_of(&router, uri).await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            body_of(&router, uri).await,
+            r#"{"error":"bad request: extra 'skip' has no value"}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn response_kind_bad_request_when_extra_fails_to_parse() {
+        let handler = Handler {
+            func: Arc::new(|path: &ResourcePath, _: &RequestContext| {
+                Box::pin(async move {
+                    let extra = CatalogExtra::from_path(path)?;
+                    assert_eq!(extra.skip, Some(10));
+                    Ok(ResourceResponse::Streams { streams: vec![] })
+                })
+            }),
+            ..streams_handler("stream")
+        };
+        let router = Router::new(
+            test_util::stream_manifest(),
+            vec![handler],
+            ServerOptions::default(),
+        );
+        let status = test_util::status_of(
+            &router,
+            "http://127.0.0.1:7070/stream/movie/tt1/skip=10.json",
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let uri = "http://127.0.0.1:7070/stream/movie/tt1/skip=ten.json";
+        assert_eq!(
+            test_util::status_of(&router, uri).await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            body_of(&router, uri).await,
+            r#"{"error":"bad request: extra 'skip' has invalid value 'ten': invalid digit found in string"}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn response_kind_bad_request_when_extra_not_declared_for_catalog() {
+        let manifest = Manifest {
+            types: vec!["movie".into()],
+            catalogs: vec![ManifestCatalog {
+                r#type: "movie".into(),
+                id: "search".into(),
+                name: None,
+                extra: ManifestExtra::Short {
+                    required: vec!["search".into()],
+                    supported: vec!["search".into()],
+                },
+            }],
+            ..test_util::manifest()
+        };
+        let router = Router::new(manifest, vec![metas_handler()], ServerOptions::default());
+        let uri = "http://127.0.0.1:7070/catalog/movie/search/search=office.json";
+        assert_eq!(test_util::status_of(&router, uri).await, StatusCode::OK);
+        let uri = "http://127.0.0.1:7070/catalog/movie/search.json";
+        assert_eq!(
+            test_util::status_of(&router, uri).await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            body_of(&router, uri).await,
+            r#"{"error":"bad request: extra 'search' is required"}"#
+        );
+        let uri = "http://127.0.0.1:7070/catalog/movie/search/search=office&genre=Drama.json";
+        assert_eq!(
+            body_of(&router, uri).await,
+            r#"{"error":"bad request: extra 'genre' is not declared for catalog 'movie/search'"}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn response_kind_json_when_addon_catalog_path() {
+        let handler = Handler {
+            name: "addon_catalog".into(),
+            r#type: None,
+            id_prefix: None,
+            id: None,
+            func: Arc::new(|_: &ResourcePath, _: &RequestContext| {
+                Box::pin(future::ready(Ok(ResourceResponse::Addons {
+                    addons: vec![],
+                })))
+            }),
+        };
+        let manifest = Manifest {
+            addon_catalogs: vec![ManifestCatalog {
+                r#type: "other".into(),
+                id: "community".into(),
+                name: None,
+                extra: ManifestExtra::Full { props: vec![] },
+            }],
+            ..test_util::manifest()
+        };
+        let router = Router::new(manifest, vec![handler], ServerOptions::default());
+        let response = router
+            .handle(
+                Request::builder()
+                    .uri("http://127.0.0.1:7070/addon_catalog/other/community.json")
+                    .body(())
+                    .unwrap(),
+            )
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.body(), r#"{"addons":[]}"#);
+    }
+
+    #[tokio::test]
+    async fn dispatch_uses_manifest_types_for_short_resource() {
+        let router = test_util::stream_router();
+        assert_eq!(
+            test_util::status_of(&router, "http://127.0.0.1:7070/stream/movie/tt1.json").await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            test_util::status_of(&router, "http://127.0.0.1:7070/stream/series/tt1.json").await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn dispatch_uses_manifest_id_prefixes_for_short_resource() {
+        let manifest = Manifest {
+            id_prefixes: Some(vec!["tt".into(), "kitsu:".into()]),
+            ..test_util::stream_manifest()
+        };
+        let router = Router::new(
+            manifest,
+            vec![streams_handler("stream")],
+            ServerOptions::default(),
+        );
+        assert_eq!(
+            test_util::status_of(&router, "http://127.0.0.1:7070/stream/movie/tt1.json").await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            test_util::status_of(&router, "http://127.0.0.1:7070/stream/movie/kitsu:1.json").await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            test_util::status_of(&router, "http://127.0.0.1:7070/stream/movie/yt1.json").await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn dispatch_uses_resource_types_for_full_resource() {
+        let manifest = Manifest {
+            types: vec!["movie".into(), "series".into()],
+            resources: vec![ManifestResource::Full {
+                name: "stream".into(),
+                types: Some(vec!["series".into()]),
+                id_prefixes: None,
+            }],
+            ..test_util::manifest()
+        };
+        let router = Router::new(
+            manifest,
+            vec![streams_handler("stream")],
+            ServerOptions::default(),
+        );
+        assert_eq!(
+            test_util::status_of(&router, "http://127.0.0.1:7070/stream/series/tt1.json").await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            test_util::status_of(&router, "http://127.0.0.1:7070/stream/movie/tt1.json").await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn dispatch_falls_back_to_manifest_types_for_full_resource() {
+        let manifest = Manifest {
+            types: vec!["movie".into()],
+            resources: vec![ManifestResource::Full {
+                name: "stream".into(),
+                types: None,
+                id_prefixes: Some(vec!["tt".into()]),
+            }],
+            ..test_util::manifest()
+        };
+        let router = Router::new(
+            manifest,
+            vec![streams_handler("stream")],
+            ServerOptions::default(),
+        );
+        assert_eq!(
+            test_util::status_of(&router, "http://127.0.0.1:7070/stream/movie/tt1.json").await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            test_util::status_of(&router, "http://127.0.0.1:7070/stream/series/tt1.json").await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn dispatch_uses_resource_id_prefixes_for_full_resource() {
+        let manifest = Manifest {
+            types: vec!["movie".into()],
+            resources: vec![ManifestResource::Full {
+                name: "stream".into(),
+                types: None,
+                id_prefixes: Some(vec!["yt".into()]),
+            }],
+            id_prefixes: Some(vec!["tt".into()]),
+            ..test_util::manifest()
+        };
+        let router = Router::new(
+            manifest,
+            vec![streams_handler("stream")],
+            ServerOptions::default(),
+        );
+        assert_eq!(
+            test_util::status_of(&router, "http://127.0.0.1:7070/stream/movie/yt1.json").await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            test_util::status_of(&router, "http://127.0.0.1:7070/stream/movie/tt1.json").await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn dispatch_falls_back_to_manifest_id_prefixes_for_full_resource() {
+        let manifest = Manifest {
+            types: vec!["movie".into()],
+            resources: vec![ManifestResource::Full {
+                name: "stream".into(),
+                types: Some(vec!["movie".into()]),
+                id_prefixes: None,
+            }],
+            id_prefixes: Some(vec!["tt".into()]),
+            ..test_util::manifest()
+        };
+        let router = Router::new(
+            manifest,
+            vec![streams_handler("stream")],
+            ServerOptions::default(),
+        );
+        assert_eq!(
+            test_util::status_of(&router, "http://127.0.0.1:7070/stream/movie/tt1.json").await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            test_util::status_of(&router, "http://127.0.0.1:7070/stream/movie/yt1.json").await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn dispatch_not_found_when_resource_not_declared() {
+        let manifest = Manifest {
+            resources: vec![ManifestResource::Short("meta".into())],
+            ..test_util::stream_manifest()
+        };
+        let router = Router::new(
+            manifest,
+            vec![streams_handler("stream")],
+            ServerOptions::default(),
+        );
+        assert_eq!(
+            test_util::status_of(&router, "http://127.0.0.1:7070/stream/movie/tt1.json").await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn dispatch_uses_declared_catalogs() {
+        let manifest = Manifest {
+            types: vec!["movie".into()],
+            catalogs: vec![ManifestCatalog {
+                r#type: "movie".into(),
+                id: "top".into(),
+                name: None,
+                extra: ManifestExtra::Full { props: vec![] },
+            }],
+            ..test_util::manifest()
+        };
+        let router = Router::new(manifest, vec![metas_handler()], ServerOptions::default());
+        assert_eq!(
+            test_util::status_of(&router, "http://127.0.0.1:7070/catalog/movie/top.json").await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            test_util::status_of(&router, "http://127.0.0.1:7070/catalog/movie/popular.json").await,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            test_util::status_of(&router, "http://127.0.0.1:7070/catalog/series/top.json").await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn dispatch_prefers_most_specific_binding() {
+        let manifest = Manifest {
+            types: vec!["movie".into(), "series".into(), "tv".into()],
+            ..test_util::stream_manifest()
+        };
+        let handlers = vec![
+            bound_handler(Some("series"), Some("kitsu:"), "anime"),
+            bound_handler(None, None, "fallback"),
+            bound_handler(Some("series"), None, "series"),
+            bound_handler(Some("series"), Some("kitsu:1"), "anime-1"),
+        ];
+        let router = Router::new(manifest, handlers, ServerOptions::default());
+        for (uri, name) in [
+            ("http://127.0.0.1:7070/stream/movie/tt1.json", "fallback"),
+            ("http://127.0.0.1:7070/stream/series/tt1.json", "series"),
+            ("http://127.0.0.1:7070/stream/series/kitsu:2.json", "anime"),
+            (
+                "http://127.0.0.1:7070/stream/series/kitsu:12.json",
+                "anime-1",
+            ),
+        ] {
+            let body = body_of(&router, uri).await;
+            assert!(
+                body.contains(&format!(r#""name":"{}""#, name)),
+                "{}: {}",
+                uri,
+                body
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn dispatch_not_found_when_no_binding_matches() {
+        let manifest = Manifest {
+            types: vec!["movie".into(), "series".into()],
+            ..test_util::stream_manifest()
+        };
+        let handlers = vec![bound_handler(Some("series"), None, "series")];
+        let router = Router::new(manifest, handlers, ServerOptions::default());
+        assert_eq!(
+            test_util::status_of(&router, "http://127.0.0.1:7070/stream/movie/tt1.json").await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn dispatch_catalog_to_its_own_handler() {
+        let manifest = Manifest {
+            types: vec!["movie".into()],
+            ..test_util::manifest()
+        };
+        let router = Builder::new(manifest)
+            .catalog(
+                ManifestCatalog {
+                    r#type: "movie".into(),
+                    id: "top".into(),
+                    name: None,
+                    extra: ManifestExtra::Full { props: vec![] },
+                },
+                |_: &ResourcePath| Ok(vec![]),
+            )
+            .catalog(
+                ManifestCatalog {
+                    r#type: "movie".into(),
+                    id: "empty".into(),
+                    name: None,
+                    extra: ManifestExtra::Full { props: vec![] },
+                },
+                |_: &ResourcePath, _: &RequestContext| {
+                    Box::pin(future::ready(Err(HandlerError::NotFound)))
+                },
+            )
+            .build(ServerOptions::default());
+        assert_eq!(
+            test_util::status_of(&router, "http://127.0.0.1:7070/catalog/movie/top.json").await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            test_util::status_of(&router, "http://127.0.0.1:7070/catalog/movie/empty.json").await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[cfg(feature = "serverless-vercel")]
+    #[tokio::test]
+    async fn serverless_responses_match_handle() {
+        let router = Router::new(
+            test_util::stream_manifest(),
+            vec![],
+            ServerOptions::default(),
+        );
+        let uri = "http://127.0.0.1:7070/manifest.json";
+        let response = router
+            .handle(Request::builder().uri(uri).body(()).unwrap())
+            .await;
+        let mut req = ServerlessRequest::new(Body::Empty);
+        *req.uri_mut() = uri.parse().unwrap();
+        let serverless = serve_serverless(router, req).await.unwrap();
+        assert_eq!(serverless.status().as_u16(), response.status().as_u16());
+        assert_eq!(serverless.headers().len(), response.headers().len());
+        for (name, value) in serverless.headers() {
+            assert_eq!(response.headers()[name.as_str()], value.as_bytes());
+        }
+        assert!(matches!(serverless.body(), Body::Text(text) if response.body() == text));
+    }
+
+    #[tokio::test]
+    async fn handlers_receive_shared_state() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let router = Builder::new(test_util::stream_manifest())
+            .with_state(calls.clone())
+            .stream(|State(calls): State<Arc<AtomicUsize>>, _: &ResourcePath| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Vec::<Stream>::new()
+            })
+            .build(ServerOptions::default());
+        let uri = "http://127.0.0.1:7070/stream/movie/tt1.json";
+        assert_eq!(test_util::status_of(&router, uri).await, StatusCode::OK);
+        assert_eq!(test_util::status_of(&router, uri).await, StatusCode::OK);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        #[cfg(feature = "serverless-vercel")]
+        {
+            let mut req = ServerlessRequest::new(Body::Empty);
+            *req.uri_mut() = uri.parse().unwrap();
+            let response = serve_serverless(router.clone(), req).await.unwrap();
+            assert_eq!(response.status(), 200);
+            assert_eq!(calls.load(Ordering::SeqCst), 3);
+        }
+    }
+
+    fn failing_handler(err: fn() -> HandlerError) -> Handler {
+        Handler {
+            name: "stream".into(),
+            r#type: None,
+            id_prefix: None,
+            id: None,
+            func: Arc::new(move |_: &ResourcePath, _: &RequestContext| {
+                Box::pin(future::ready(Err(err())))
+            }),
+        }
+    }
+
+    #[tokio::test]
+    async fn response_kind_error_when_handler_fails() {
+        for (err, status, body) in [
+            (
+                (|| HandlerError::BadRequest("invalid id".into())) as fn() -> HandlerError,
+                StatusCode::BAD_REQUEST,
+                r#"{"error":"bad request: invalid id"}"#,
+            ),
+            (
+                || HandlerError::upstream("connection refused"),
+                StatusCode::BAD_GATEWAY,
+                r#"{"error":"upstream service failed"}"#,
+            ),
+            (
+                || HandlerError::RateLimited { retry_after: None },
+                StatusCode::TOO_MANY_REQUESTS,
+                r#"{"error":"rate limited"}"#,
+            ),
+        ] {
+            let router = Router::new(
+                test_util::stream_manifest(),
+                vec![failing_handler(err)],
+                ServerOptions::default(),
+            );
+            let response = router
+                .handle(
+                    Request::builder()
+                        .uri("http://127.0.0.1:7070/stream/movie/tt1.json")
+                        .body(())
+                        .unwrap(),
+                )
+                .await;
+            assert_eq!(response.status(), status);
+            assert_eq!(response.body(), body);
+            assert_eq!(
+                response.headers().get(header::CONTENT_TYPE).unwrap(),
+                HeaderValue::from_static("application/json")
+            );
+            assert!(response.headers().get(header::CACHE_CONTROL).is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn response_has_retry_after_when_rate_limited() {
+        let handler = failing_handler(|| HandlerError::RateLimited {
+            retry_after: Some(Duration::from_secs(30)),
+        });
+        let router = Router::new(
+            test_util::stream_manifest(),
+            vec![handler],
+            ServerOptions::default(),
+        );
+        let response = router
+            .handle(
+                Request::builder()
+                    .uri("http://127.0.0.1:7070/stream/movie/tt1.json")
+                    .body(())
+                    .unwrap(),
+            )
+            .await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(
+            response.headers().get(header::RETRY_AFTER).unwrap(),
+            HeaderValue::from_static("30")
+        );
+    }
+
+    #[tokio::test]
+    async fn response_kind_error_when_handler_panics() {
+        let panicking = Handler {
+            name: "stream".into(),
+            r#type: Some("movie".into()),
+            id_prefix: None,
+            id: None,
+            func: Arc::new(|_: &ResourcePath, _: &RequestContext| panic!("handler failed")),
+        };
+        let panicking_future = Handler {
+            name: "stream".into(),
+            r#type: Some("series".into()),
+            id_prefix: None,
+            id: None,
+            func: Arc::new(|_: &ResourcePath, _: &RequestContext| {
+                Box::pin(async { panic!("future failed") })
+            }),
+        };
+        let manifest = Manifest {
+            types: vec!["movie".into(), "series".into(), "tv".into()],
+            ..test_util::stream_manifest()
+        };
+        let router = Router::new(
+            manifest,
+            vec![panicking, panicking_future, streams_handler("stream")],
+            ServerOptions::default(),
+        );
+        for uri in [
+            "http://127.0.0.1:7070/stream/movie/tt1.json",
+            "http://127.0.0.1:7070/stream/series/tt1.json",
+        ] {
+            let response = router
+                .handle(Request::builder().uri(uri).body(()).unwrap())
+                .await;
+            assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+            assert_eq!(response.body(), r#"{"error":"internal server error"}"#);
+            assert!(response.headers().get(header::CACHE_CONTROL).is_none());
+        }
+        #[cfg(feature = "serverless-vercel")]
+        {
+            let mut serverless = ServerlessRequest::new(Body::Empty);
+            *serverless.uri_mut() = "http://127.0.0.1:7070/stream/movie/tt1.json"
+                .parse()
+                .unwrap();
+            let response = serve_serverless(router.clone(), serverless).await.unwrap();
+            assert_eq!(response.status().as_u16(), 500);
+        }
+        assert_eq!(
+            test_util::status_of(&router, "http://127.0.0.1:7070/stream/tv/tt1.json").await,
+            StatusCode::OK
+        );
+    }
 }
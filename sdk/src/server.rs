@@ -1,24 +1,66 @@
+#[cfg(feature = "server-hyper")]
+use std::convert::Infallible;
+#[cfg(any(
+    feature = "server-hyper",
+    feature = "serverless-vercel",
+    feature = "lambda"
+))]
 use std::error::Error;
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+#[cfg(feature = "server-hyper")]
+use std::net::SocketAddr;
+use std::net::{IpAddr, Ipv4Addr};
+#[cfg(feature = "server-hyper")]
 use std::sync::Arc;
 
+#[cfg(feature = "server-hyper")]
 use hyper::server::conn::http1;
+#[cfg(feature = "server-hyper")]
 use hyper::service::service_fn;
+#[cfg(feature = "server-hyper")]
+use hyper::{Request, Response};
+#[cfg(feature = "server-hyper")]
 use hyper_util::rt::TokioIo;
+#[cfg(feature = "server-hyper")]
 use tokio::net::TcpListener;
+#[cfg(feature = "serverless-vercel")]
 use vercel_runtime::Body;
 
-use crate::request::{HyperRequest, Request, ServerlessRequest};
-use crate::response::Response;
-use crate::response::{HyperResponse, ServerlessResponse};
+#[cfg(feature = "cgi")]
+pub use crate::cgi::serve_cgi;
+#[cfg(feature = "fastcgi")]
+pub use crate::fastcgi::{serve_fastcgi, FastCgiListener};
+#[cfg(feature = "lambda")]
+use crate::lambda::{self, LambdaRequest, LambdaResponse};
+#[cfg(feature = "serverless-vercel")]
+use crate::request::{self, ServerlessRequest};
+#[cfg(any(
+    feature = "server-hyper",
+    feature = "serverless-vercel",
+    feature = "lambda"
+))]
+use crate::response;
+#[cfg(feature = "serverless-vercel")]
+use crate::response::ServerlessResponse;
+#[cfg(any(
+    feature = "server-hyper",
+    feature = "serverless-vercel",
+    feature = "lambda"
+))]
 use crate::router::Router;
 
+/// Options of the router and of the servers it runs on. New options can be added without a major
+/// release, so they are set on `ServerOptions::default()` rather than listed in a struct literal.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct ServerOptions {
     pub ip: IpAddr,
     pub port: u16,
     pub cache_max_age: i32,
     pub index_html: String,
+    /// Takes the client address of `RequestContext::client_ip` from the first `X-Forwarded-For`
+    /// entry, for addons behind a proxy which sets it. Clients can send any value otherwise.
+    /// `serve_serverless` always takes it, as Vercel's edge sets it for every request.
+    pub trust_forwarded_for: bool,
 }
 
 impl Default for ServerOptions {
@@ -28,52 +70,74 @@ impl Default for ServerOptions {
             port: 43001,
             cache_max_age: 24 * 3600 * 3, // cache 3 days,
             index_html: include_str!("../res/index.html").into(),
+            trust_forwarded_for: false,
         }
     }
 }
 
+impl ServerOptions {
+    /// Sets `trust_forwarded_for`.
+    pub fn with_trust_forwarded_for(self, trust_forwarded_for: bool) -> Self {
+        Self {
+            trust_forwarded_for,
+            ..self
+        }
+    }
+}
+
+#[cfg(feature = "server-hyper")]
 pub async fn serve_http(
     router: Router,
-) -> Result<HyperResponse<String>, Box<dyn Error + Send + Sync + 'static>> {
+) -> Result<Response<String>, Box<dyn Error + Send + Sync + 'static>> {
     let options = router.options();
     let addr = SocketAddr::new(options.ip, options.port);
     let listener = TcpListener::bind(addr).await?;
-    println!("Running on: {}", addr);
+    log::info!("Running on: {}", addr);
     loop {
-        let stream = listener.accept().await?.0;
+        let (stream, remote_addr) = listener.accept().await?;
         let io = TokioIo::new(stream);
         let router_arc = Arc::new(router.clone());
-        let service = service_fn(move |req: HyperRequest<hyper::body::Incoming>| {
-            println!("Incoming request: {}", req.uri());
+        let service = service_fn(move |mut req: Request<hyper::body::Incoming>| {
+            log::debug!("Incoming request: {}", req.uri());
+            req.extensions_mut().insert(remote_addr);
             let router_arc_clone = router_arc.clone();
             async move {
-                router_arc_clone
-                    .route(Request::Hyper(req))
-                    .await
-                    .map(|res: Response<String>| match res {
-                        Response::Hyper(res) => res,
-                        _ => unreachable!(),
-                    })
+                let response = router_arc_clone.handle(req).await;
+                Ok::<_, Infallible>(response::into_text(response))
             }
         });
         tokio::task::spawn(async move {
             if let Err(err) = http1::Builder::new().serve_connection(io, service).await {
-                eprintln!("connection error: {:?}", err);
+                log::error!("connection error: {:?}", err);
             }
         });
     }
 }
 
+#[cfg(feature = "serverless-vercel")]
 pub async fn serve_serverless(
     router: Router,
     request: ServerlessRequest,
 ) -> Result<ServerlessResponse<Body>, Box<dyn Error + Send + Sync + 'static>> {
-    router
-        .route::<Body, Body>(Request::Serverless(request))
-        .await
-        .map(|res: Response<Body>| match res {
-            Response::Serverless(res) => res,
-            _ => unreachable!(),
-        })
-        .map_err(|e| e.into())
+    let request = request::from_serverless(&request)?;
+    response::into_lambda(router.handle(request).await)
+}
+
+/// Answers a request of AWS Lambda, behind API Gateway or a function URL:
+///
+/// ```no_run
+/// # use stremio_addon_sdk::lambda_http::{self, service_fn};
+/// # use stremio_addon_sdk::router::Router;
+/// # use stremio_addon_sdk::server::serve_lambda;
+/// # async fn run(router: Router) -> Result<(), lambda_http::Error> {
+/// lambda_http::run(service_fn(|request| serve_lambda(router.clone(), request))).await
+/// # }
+/// ```
+#[cfg(feature = "lambda")]
+pub async fn serve_lambda(
+    router: Router,
+    request: LambdaRequest,
+) -> Result<LambdaResponse<lambda_http::Body>, Box<dyn Error + Send + Sync + 'static>> {
+    let request = lambda::from_lambda(&request)?;
+    response::into_lambda(router.handle(request).await)
 }
@@ -0,0 +1,71 @@
+use std::convert::Infallible;
+use std::task::{Context, Poll};
+
+use futures::future::BoxFuture;
+use http::{Request, Response};
+use tower::Service;
+
+use crate::response;
+use crate::router::Router;
+
+/// Serves the addon from a tower stack, e.g. nested in an axum app with
+/// `axum::Router::nest_service("/addon", router)`. Requests are answered by [`Router::handle`],
+/// so the service never fails.
+impl<B> Service<Request<B>> for Router
+where
+    B: Send + 'static,
+{
+    type Response = Response<String>;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let router = self.clone();
+        Box::pin(async move { Ok(response::into_text(router.handle(request).await)) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{Method, Request, StatusCode};
+    use tower::ServiceExt;
+
+    use crate::test_util;
+
+    #[tokio::test]
+    async fn router_is_a_tower_service() {
+        let response = test_util::stream_router()
+            .oneshot(
+                Request::builder()
+                    .uri("/stream/movie/tt1.json")
+                    .body(())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.body(), r#"{"streams":[]}"#);
+
+        let response = test_util::stream_router()
+            .oneshot(Request::builder().uri("/manifest.json").body(()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = test_util::stream_router()
+            .oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri("/stream/movie/tt1.json")
+                    .body(String::from("ignored"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+}
@@ -0,0 +1,22 @@
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// State shared by every request, set with `Builder::with_state`.
+///
+/// It is allocated once by the builder, so handing it to a handler only clones an `Arc`.
+#[derive(Debug)]
+pub struct State<S>(pub Arc<S>);
+
+impl<S> Clone for State<S> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<S> Deref for State<S> {
+    type Target = S;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
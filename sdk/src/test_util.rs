@@ -0,0 +1,49 @@
+use http::{Request, StatusCode};
+use stremio_core::types::addon::{Manifest, ManifestResource, ResourcePath};
+use stremio_core::types::resource::Stream;
+
+use crate::builder::Builder;
+use crate::router::Router;
+use crate::server::ServerOptions;
+use crate::utils;
+
+/// `utils::default_manifest` with the id and name manifest validation requires.
+pub(crate) fn manifest() -> Manifest {
+    Manifest {
+        id: "com.example.addon".into(),
+        name: "Example addon".into(),
+        ..utils::default_manifest()
+    }
+}
+
+/// `manifest` with the `stream` resource for movies.
+pub(crate) fn stream_manifest() -> Manifest {
+    Manifest {
+        types: vec!["movie".into()],
+        resources: vec![ManifestResource::Short("stream".into())],
+        ..manifest()
+    }
+}
+
+/// Router of `stream_manifest` which answers every stream request with no streams.
+pub(crate) fn stream_router() -> Router {
+    Builder::new(stream_manifest())
+        .stream(|_: &ResourcePath| Vec::<Stream>::new())
+        .build(ServerOptions::default())
+}
+
+pub(crate) async fn status_of(router: &Router, uri: &str) -> StatusCode {
+    let response = router
+        .handle(Request::builder().uri(uri).body(()).unwrap())
+        .await;
+    response.status()
+}
+
+/// Params of a CGI script or a FastCGI request.
+#[cfg(any(feature = "cgi", feature = "fastcgi"))]
+pub(crate) fn params(params: &[(&str, &str)]) -> Vec<(String, String)> {
+    params
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
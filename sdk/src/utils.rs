@@ -1,5 +1,8 @@
-use stremio_core::types::addon::Manifest;
+use std::any::Any;
 
+use stremio_core::types::addon::{Manifest, ManifestResource};
+
+/// Manifest with every field empty. Its `id` and `name` have to be set for it to pass validation.
 pub fn default_manifest() -> Manifest {
     Manifest {
         id: "".to_string(),
@@ -23,3 +26,43 @@ pub fn default_manifest() -> Manifest {
         behavior_hints: Default::default(),
     }
 }
+
+// NOTE: resource.name() should probably be public in stremio-core, making this function unnecessary
+pub(crate) fn resource_name(resource: &ManifestResource) -> &str {
+    match resource {
+        ManifestResource::Short(name) => name,
+        ManifestResource::Full { name, .. } => name,
+    }
+}
+
+/// Types and id prefixes the manifest declares for a resource other than the catalogs, where
+/// resource-level declarations take precedence over the manifest-level ones.
+pub(crate) fn resource_declaration<'a>(
+    manifest: &'a Manifest,
+    name: &str,
+) -> Option<(&'a [String], Option<&'a [String]>)> {
+    let resource = manifest
+        .resources
+        .iter()
+        .find(|resource| resource_name(resource) == name)?;
+    let declaration = match resource {
+        ManifestResource::Short(_) => (&manifest.types, manifest.id_prefixes.as_ref()),
+        ManifestResource::Full {
+            types, id_prefixes, ..
+        } => (
+            types.as_ref().unwrap_or(&manifest.types),
+            id_prefixes.as_ref().or(manifest.id_prefixes.as_ref()),
+        ),
+    };
+    Some((declaration.0, declaration.1.map(Vec::as_slice)))
+}
+
+pub(crate) fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
+}